@@ -1,100 +1,207 @@
-use std::{collections::HashMap, io::{stdout, Write}};
-
-use crossterm::{cursor, style::Print, terminal, QueueableCommand};
-
-use crate::{components::{position::Position, renderable::Renderable}, utils::color::Color};
+use crate::{
+    components::{
+        position::Position,
+        renderable::{Layer, Renderable},
+    },
+    render_target::{CrosstermTarget, Frame, RenderTarget},
+    utils::{attributes::Attributes, color::Color},
+};
+
+/// An empty terminal cell.
+pub const BLANK: Renderable = Renderable {
+    glyph: ' ',
+    fg: Color::Default,
+    bg: Color::Default,
+    layer: Layer::Terrain,
+    attrs: Attributes::NONE,
+};
+
+/// Stands in for cells whose content on screen is not known, so they always get redrawn.
+const UNKNOWN: Renderable = Renderable {
+    glyph: '\0',
+    fg: Color::Default,
+    bg: Color::Default,
+    layer: Layer::Terrain,
+    attrs: Attributes::NONE,
+};
 
 pub struct Camera {
     pub pos: Position,
     pub buffer: Vec<(Position, Renderable)>,
+    /// Glyphs placed in screen space (top-left origin), drawn over the world.
+    pub ui_buffer: Vec<(Position, Renderable)>,
+    /// World tiles whose background is tinted this frame, keeping whatever glyph is on them.
+    pub highlights: Vec<(Position, Color)>,
+    /// Where finished frames are drawn.
+    target: Box<dyn RenderTarget>,
+    /// The size of the target the cell grids were allocated for.
+    size: (u16, u16),
+    /// The size of the part of the screen the world is drawn in, from the top-left corner.
+    viewport: (u16, u16),
+    /// What is currently on the target, row by row.
+    front: Vec<Renderable>,
+    /// The frame being composed.
+    back: Vec<Renderable>,
+    /// Set when the target has to be wiped before the next frame is drawn.
+    needs_clear: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Camera {
+    /// Creates a camera that draws to the terminal.
     pub fn new() -> Self {
-        Camera {
+        Self::with_target(Box::new(CrosstermTarget::new()))
+    }
+
+    /// Creates a camera that draws to `target`.
+    pub fn with_target(target: Box<dyn RenderTarget>) -> Self {
+        let mut camera = Camera {
             pos: Position { x: 0, y: 0 },
             buffer: vec![],
-        }
+            ui_buffer: vec![],
+            highlights: vec![],
+            target,
+            size: (0, 0),
+            viewport: (0, 0),
+            front: vec![],
+            back: vec![],
+            needs_clear: true,
+        };
+        camera.refresh_size();
+
+        camera
     }
 
-    pub fn load_terminal_settings() {
-        let mut stdout = stdout();
+    /// Returns the width and height of the screen in cells, as of the last refresh.
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
 
-        stdout.queue(terminal::EnterAlternateScreen).unwrap();
-        stdout.queue(cursor::Hide).unwrap();
-        stdout.queue(terminal::DisableLineWrap).unwrap();
+    /// Returns the width and height of the part of the screen the world is drawn in.
+    pub fn viewport(&self) -> (u16, u16) {
+        self.viewport
+    }
 
-        // Ensure all commands are executed
-        stdout.flush().unwrap();
+    /// Limits the world to the top-left `size` cells of the screen, leaving the rest
+    /// free for the UI.
+    pub fn set_viewport(&mut self, size: (u16, u16)) {
+        self.viewport = (size.0.min(self.size.0), size.1.min(self.size.1));
     }
 
-    pub fn deload_terminal_settings() {
-        let mut stdout = stdout();
+    /// Returns the render target, if it is a `T`.
+    pub fn target<T: RenderTarget + 'static>(&self) -> Option<&T> {
+        self.target.as_any().downcast_ref::<T>()
+    }
 
-        // Leave the alternate screen, restoring the original terminal
-        stdout.queue(terminal::LeaveAlternateScreen).unwrap();
+    /// Returns the render target to change it, if it is a `T`.
+    ///
+    /// Call `refresh_size` afterwards if the change affects the target's size.
+    pub fn target_mut<T: RenderTarget + 'static>(&mut self) -> Option<&mut T> {
+        self.target.as_any_mut().downcast_mut::<T>()
+    }
 
-        // Ensure all commands are executed
-        stdout.flush().unwrap();
+    /// Asks the render target for its size, reallocating the cell grids if it changed.
+    pub fn refresh_size(&mut self) {
+        let size = self.target.size();
+        self.resize(size);
     }
 
+    /// Draws the queued glyphs to the render target.
+    ///
+    /// The frame is composed into a back buffer and handed to the target along with the
+    /// frame already on screen, so the target only has to draw the cells that changed.
     pub fn render(&mut self) {
-        let mut stdout = stdout();
+        self.compose();
+
+        self.target.present(&Frame {
+            size: self.size,
+            cells: &self.back,
+            previous: &self.front,
+            clear: self.needs_clear,
+        });
+        self.needs_clear = false;
+
+        std::mem::swap(&mut self.front, &mut self.back);
+        self.buffer.clear();
+        self.ui_buffer.clear();
+        self.highlights.clear();
+    }
 
-        let terminal_size = terminal::size().unwrap();
+    /// Forgets what is on the target, so the next frame wipes it and draws every cell.
+    pub fn redraw(&mut self) {
+        self.front.fill(UNKNOWN);
+        self.needs_clear = true;
+    }
 
-        // Create a HashMap for all positions with empty pixels
-        let mut buffer: HashMap<Position, Renderable> = HashMap::new();
+    /// Reallocates both cell grids for a new screen size, forcing a full redraw.
+    ///
+    /// Nothing happens if the size didn't actually change, so the screen is only wiped
+    /// once however many times the same size is reported.
+    pub fn resize(&mut self, size: (u16, u16)) {
+        if size == self.size && self.back.len() == size.0 as usize * size.1 as usize {
+            return;
+        }
 
-        for x in 0..terminal_size.0 {
-            for y in 0..terminal_size.1 {
-                let x = x as isize;
-                let y = y as isize;
-                let pos = Position {x, y};
+        let len = size.0 as usize * size.1 as usize;
 
-                let renderable = Renderable {
-                    glyph: ' ',
-                    fg: Color::Default,
-                    bg: Color::Default
-                };
+        self.size = size;
+        self.viewport = size;
+        self.back = vec![BLANK; len];
+        // Nothing is known to be on screen yet, so every cell counts as changed
+        self.front = vec![UNKNOWN; len];
+        self.needs_clear = true;
+    }
 
-                buffer.insert(pos, renderable);
+    /// Fills the back buffer from the world and UI glyphs queued this frame.
+    ///
+    /// Glyphs are laid down layer by layer, so higher layers always end up on top. Within
+    /// a layer, the glyph queued last wins. Highlights tint the world before the UI goes
+    /// on top of it.
+    fn compose(&mut self) {
+        self.back.fill(BLANK);
 
-            }
-        }
+        // Stable sorts, so queue order still breaks ties within a layer
+        self.buffer.sort_by_key(|(_, renderable)| renderable.layer);
+        self.ui_buffer.sort_by_key(|(_, renderable)| renderable.layer);
 
         for (pos, renderable) in &self.buffer {
-            let mut adjusted_pos: Position = *pos - self.pos;
-            adjusted_pos.y = terminal_size.1 as isize - adjusted_pos.y - 1;
-            // adjusted_pos.y = 0;
-
-            if !Self::is_visible(terminal_size, adjusted_pos) {
-                continue;
+            if let Some(idx) = self.world_cell_index(*pos) {
+                self.back[idx] = *renderable;
             }
+        }
 
-            buffer.insert(adjusted_pos, *renderable);
-
-            // stdout
-            //     .queue(cursor::MoveTo(adjusted_pos.x as u16, adjusted_pos.y as u16))
-            //     .unwrap();
-            // stdout.queue(Print(renderable.to_string())).unwrap();
-            // print!("{adjusted_pos:?}");
+        for (pos, color) in &self.highlights {
+            if let Some(idx) = self.world_cell_index(*pos) {
+                self.back[idx].bg = *color;
+            }
         }
-        // stdout.queue(cursor::MoveTo(0, 0));
-        // print!("{:?}", self.pos);
 
-        for (pos, renderable) in buffer {
-            stdout
-                .queue(cursor::MoveTo(pos.x as u16, pos.y as u16))
-                .unwrap();
+        for (pos, renderable) in &self.ui_buffer {
+            if let Some(idx) = self.cell_index(*pos) {
+                self.back[idx] = *renderable;
+            }
+        }
+    }
 
-            stdout.queue(Print(renderable.to_string())).unwrap();
+    /// Returns the cell a world position is drawn in, or `None` if it is outside the viewport.
+    fn world_cell_index(&self, pos: Position) -> Option<usize> {
+        let mut adjusted_pos: Position = pos - self.pos;
+        adjusted_pos.y = self.viewport.1 as isize - adjusted_pos.y - 1;
 
+        if !Self::is_visible(self.viewport, adjusted_pos) {
+            return None;
         }
 
-        stdout.flush().unwrap();
+        self.cell_index(adjusted_pos)
+    }
 
-        self.buffer.clear();
+    fn cell_index(&self, pos: Position) -> Option<usize> {
+        Self::is_visible(self.size, pos).then(|| pos.y as usize * self.size.0 as usize + pos.x as usize)
     }
 
     pub fn is_visible(terminal_size: (u16, u16), pos: Position) -> bool {
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Makes a ranged item hit everything within `radius` tiles of its target, instead of
+/// just what stands on the target tile.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct AreaOfEffect {
+    pub radius: i32,
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Marks an entity that nothing else can walk through.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct BlocksTile {}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Health and fighting strength of anything that can take part in melee.
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CombatStats {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+impl CombatStats {
+    /// Creates stats for an entity at full health.
+    pub fn new(max_hp: i32, defense: i32, power: i32) -> Self {
+        CombatStats {
+            max_hp,
+            hp: max_hp,
+            defense,
+            power,
+        }
+    }
+}
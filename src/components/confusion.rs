@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// On an item, confuses the monsters it is used on for `turns` turns.
+///
+/// On a monster, it is confused: it loses its next `turns` actions, and the component is
+/// removed once they are spent.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Confusion {
+    pub turns: i32,
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Marks an item that is used up the first time it is used.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Consumable {}
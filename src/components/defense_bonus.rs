@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Adds `defense` to whoever has the item equipped.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct DefenseBonus {
+    pub defense: i32,
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// How much energy a single action costs.
+pub const ACTION_COST: i32 = 100;
+
+/// Lets an entity take part in the turn scheduler.
+///
+/// Every tick of game time each entity gains `speed` energy, and it may act whenever it
+/// has at least `ACTION_COST` saved up. An entity with a speed of `200` acts twice for
+/// every action of the player's `100`, and one with `50` acts every other turn.
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Energy {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+impl Energy {
+    /// Creates an entity's energy pool, ready to act straight away.
+    pub fn new(speed: i32) -> Self {
+        Energy {
+            speed,
+            energy: ACTION_COST,
+        }
+    }
+
+    pub fn can_act(&self) -> bool {
+        self.energy >= ACTION_COST
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Where on the body an item is worn. Only one item can be equipped in each slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Melee,
+    Shield,
+    Armour,
+    Ring,
+}
+
+impl EquipmentSlot {
+    /// Every slot, in the order the equipment screen lists them.
+    pub const ALL: [EquipmentSlot; 4] = [
+        EquipmentSlot::Melee,
+        EquipmentSlot::Shield,
+        EquipmentSlot::Armour,
+        EquipmentSlot::Ring,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Melee => "Weapon",
+            EquipmentSlot::Shield => "Shield",
+            EquipmentSlot::Armour => "Armour",
+            EquipmentSlot::Ring => "Ring",
+        }
+    }
+}
+
+/// Marks an item that can be equipped in `slot`.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
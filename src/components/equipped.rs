@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use specs::{
+    prelude::*,
+    saveload::{ConvertSaveload, Marker},
+};
+use specs_derive::{Component, ConvertSaveload};
+
+use super::{equippable::EquipmentSlot, NoError};
+
+/// Holds an item that `owner` has equipped in `slot`.
+///
+/// Equipped items are neither on the floor nor in the backpack, so they have no
+/// `Position` and no `InBackpack`.
+#[derive(Component, ConvertSaveload, Debug, Clone)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
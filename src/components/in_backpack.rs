@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use specs::{
+    prelude::*,
+    saveload::{ConvertSaveload, Marker},
+};
+use specs_derive::{Component, ConvertSaveload};
+
+use super::NoError;
+
+/// How many items the player can carry: one for each letter the item menus list them
+/// under.
+pub const BACKPACK_CAPACITY: usize = 26;
+
+/// Holds an item that is being carried by `owner`.
+///
+/// Carried items have no `Position`, so they are never drawn and follow their owner
+/// from floor to floor.
+#[derive(Component, ConvertSaveload, Debug, Clone)]
+pub struct InBackpack {
+    pub owner: Entity,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Makes an item deal `damage` to everything it is used on.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct InflictsDamage {
+    pub damage: i32,
+}
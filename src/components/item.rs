@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Marks an entity that can be picked up and carried.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Item {}
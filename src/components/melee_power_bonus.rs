@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Adds `power` to the melee attacks of whoever has the item equipped.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct MeleePowerBonus {
+    pub power: i32,
+}
@@ -1,2 +1,31 @@
+pub mod area_of_effect;
+pub mod blocks_tile;
+pub mod combat_stats;
+pub mod confusion;
+pub mod consumable;
+pub mod defense_bonus;
+pub mod energy;
+pub mod equippable;
+pub mod equipped;
+pub mod in_backpack;
+pub mod inflicts_damage;
+pub mod item;
+pub mod melee_power_bonus;
+pub mod monster;
+pub mod name;
+pub mod off_floor;
 pub mod position;
+pub mod provides_healing;
+pub mod ranged;
 pub mod renderable;
+pub mod suffer_damage;
+pub mod viewshed;
+pub mod wants_to_drop_item;
+pub mod wants_to_equip_item;
+pub mod wants_to_melee;
+pub mod wants_to_pickup_item;
+pub mod wants_to_unequip_item;
+pub mod wants_to_use_item;
+
+/// The error type the `ConvertSaveload` derive expects, under its old name.
+pub(crate) use std::convert::Infallible as NoError;
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Marks an entity as a monster, driven by the `MonsterAI` system.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Monster {}
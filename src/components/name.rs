@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Name {
+    pub name: String,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+use super::position::Position;
+
+/// Holds the position of an entity that lives on a floor the player is not on.
+///
+/// The entity's `Position` is taken away while it is off floor, so every system that
+/// joins on `Position` leaves it alone until the player comes back to its floor.
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OffFloor {
+    pub depth: usize,
+    pub pos: Position,
+}
@@ -1,8 +1,9 @@
 use std::ops::{Add, Div, Sub};
 
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs_derive::Component;
-#[derive(Component, Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[derive(Component, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub x: isize,
     pub y: isize,
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Makes an item restore `heal_amount` hp to whoever uses it, up to their maximum.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct ProvidesHealing {
+    pub heal_amount: i32,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Makes an item aimed at a tile up to `range` tiles away, picked by the player before
+/// it is used.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Ranged {
+    pub range: i32,
+}
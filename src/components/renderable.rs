@@ -1,22 +1,41 @@
-use std::fmt;
-
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs_derive::Component;
 
-use crate::utils::color::Color;
+use crate::utils::{
+    attributes::Attributes,
+    color::{Color, ColorSupport},
+};
+
+/// The layer a glyph is drawn on. When two glyphs land on the same cell, the one on the
+/// higher layer wins, no matter which was queued first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Layer {
+    Terrain,
+    Items,
+    Actors,
+    Effects,
+    Ui,
+}
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Renderable {
     pub glyph: char,
     pub fg: Color,
     pub bg: Color,
+    pub layer: Layer,
+    pub attrs: Attributes,
 }
 
-impl fmt::Display for Renderable {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let fg_code = self.fg.to_code();
-        let bg_code = self.bg.to_code();
+impl Renderable {
+    /// Returns the escape sequence that resets the terminal's style and switches to this
+    /// glyph's colours and attributes, downsampling the colours to what `support` allows.
+    pub fn style_escape(&self, support: ColorSupport) -> String {
+        let mut params: Vec<String> = vec!["0".to_string()];
+        params.extend(self.attrs.sgr_params().map(|code| code.to_string()));
+        params.push(self.fg.downsample(support).sgr_params(false));
+        params.push(self.bg.downsample(support).sgr_params(true));
 
-        write!(f, "\x1b[3{};4{}m{}\x1b[0m", fg_code, bg_code, self.glyph)
+        format!("\x1b[{}m", params.join(";"))
     }
 }
@@ -0,0 +1,23 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Damage an entity has taken this turn, applied and removed by the `DamageSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct SufferDamage {
+    pub amount: Vec<i32>,
+}
+
+impl SufferDamage {
+    /// Adds `amount` to the damage `victim` takes this turn, so several hits in the same
+    /// turn all count.
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+        if let Some(suffering) = store.get_mut(victim) {
+            suffering.amount.push(amount);
+        } else {
+            let dmg = SufferDamage {
+                amount: vec![amount],
+            };
+            store.insert(victim, dmg).expect("Unable to insert damage");
+        }
+    }
+}
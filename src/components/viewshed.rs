@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::Component;
+
+use super::position::Position;
+
+/// What an entity can see from where it stands.
+///
+/// The `VisibilitySystem` only recomputes `visible_tiles` while `dirty` is set, so anything
+/// that moves the entity should mark its viewshed dirty.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Viewshed {
+    /// How far the entity can see, in tiles.
+    pub range: i32,
+    pub visible_tiles: Vec<Position>,
+    pub dirty: bool,
+}
+
+impl Viewshed {
+    pub fn new(range: i32) -> Self {
+        Viewshed {
+            range,
+            visible_tiles: Vec::new(),
+            dirty: true,
+        }
+    }
+}
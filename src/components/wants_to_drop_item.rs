@@ -0,0 +1,9 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Queued by anything that wants to put `item` from its backpack down where it stands.
+/// Resolved and removed by the `ItemDropSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToDropItem {
+    pub item: Entity,
+}
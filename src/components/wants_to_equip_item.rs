@@ -0,0 +1,9 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Queued by anything that wants to equip `item` from its backpack this turn.
+/// Resolved and removed by the `ItemEquipSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToEquipItem {
+    pub item: Entity,
+}
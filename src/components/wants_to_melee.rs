@@ -0,0 +1,9 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Queued by anything that wants to attack `target` this turn.
+/// Resolved and removed by the `MeleeCombatSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToMelee {
+    pub target: Entity,
+}
@@ -0,0 +1,10 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Queued when `collected_by` wants to pick `item` up off the floor.
+/// Resolved and removed by the `ItemCollectionSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToPickupItem {
+    pub collected_by: Entity,
+    pub item: Entity,
+}
@@ -0,0 +1,9 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+/// Queued by anything that wants to take off the equipped `item` and put it back in its
+/// backpack. Resolved and removed by the `ItemEquipSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToUnequipItem {
+    pub item: Entity,
+}
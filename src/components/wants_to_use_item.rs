@@ -0,0 +1,12 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+use super::position::Position;
+
+/// Queued by anything that wants to use `item` from its backpack this turn, aimed at
+/// `target` for ranged items. Resolved and removed by the `ItemUseSystem`.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<Position>,
+}
@@ -3,13 +3,20 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crossterm::terminal;
-
 use crate::{
     camera::Camera,
-    components::{position::Position, renderable::Renderable},
-    utils::input_handler::InputHandler,
-    GameState, State,
+    components::{
+        position::Position,
+        renderable::{Layer, Renderable},
+    },
+    gui,
+    render_target::HeadlessTarget,
+    screen::Screen,
+    terminal,
+    utils::{
+        attributes::Attributes, color::Color, input_handler::InputHandler, settings::Settings,
+    },
+    State,
 };
 
 const FPS: u64 = 20; // Desired frames per second
@@ -18,6 +25,15 @@ const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS);
 pub struct Ctx {
     pub cam: Camera,
     pub input_handler: InputHandler,
+    pub settings: Settings,
+    /// The screens being shown, bottom first. The game ends once there are none left.
+    screens: Vec<Screen>,
+}
+
+impl Default for Ctx {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Ctx {
@@ -25,25 +41,69 @@ impl Ctx {
         Ctx {
             cam: Camera::new(),
             input_handler: InputHandler::new(),
+            settings: Settings::default(),
+            screens: vec![],
+        }
+    }
+
+    /// Creates a context that renders into memory instead of the terminal and never reads
+    /// from it, for running the game without a TTY.
+    ///
+    /// Input is fed in through `InputHandler::push_event`, and frames are run one at a
+    /// time with `frame`.
+    pub fn headless(width: u16, height: u16) -> Self {
+        Ctx {
+            cam: Camera::with_target(Box::new(HeadlessTarget::new(width, height))),
+            input_handler: InputHandler::headless(),
+            settings: Settings::default(),
+            screens: vec![],
         }
     }
 
-    pub fn main_loop(&mut self, mut gs: State) {
+    /// Runs the game until every screen has been closed, starting with `screens`
+    /// (bottom first).
+    ///
+    /// Every frame is run with `frame`, at up to `FPS` frames per second.
+    pub fn main_loop(&mut self, gs: &mut State, screens: Vec<Screen>) {
         let mut last_frame_time = Instant::now();
 
+        self.screens = screens;
         self.input_handler.start();
 
         '_game_loop: loop {
-            if self.should_stop() {
+            // Without the reader thread no key can ever arrive, so the game can only end
+            // here, through the same way out as a quit
+            if self.should_stop()
+                || terminal::take_quit_request()
+                || self.input_handler.reader_died()
+            {
+                self.screens.clear();
+            }
+
+            if self.should_suspend() || terminal::take_suspend_request() {
+                terminal::suspend();
+            }
+
+            // Whatever stopped the game may have drawn over the screen, or resized it
+            if terminal::take_resume() {
+                // Keys let go of while the game was stopped never report their release
+                self.input_handler.release_keys();
+                self.cam.refresh_size();
+                self.cam.redraw();
+            }
+
+            if let Some(size) = self.input_handler.take_resize() {
+                self.cam.resize(size);
+            }
+
+            if self.screens.is_empty() {
                 self.input_handler.stop(); // Stop the input handling thread.
                 break; // Exit the game loop.
             }
 
             let now = Instant::now();
 
-            gs.tick(self);
-
-            self.cam.render();
+            self.frame(gs);
 
             // Calculate elapsed time for this frame
             let elapsed = now.duration_since(last_frame_time);
@@ -57,21 +117,125 @@ impl Ctx {
         }
     }
 
+    /// Runs a single frame: the top screen handles the input, then every screen that can
+    /// be seen is drawn, bottom first, and the result is rendered. While the terminal is
+    /// too small for the game, a message asking for more room is shown instead.
+    ///
+    /// Does nothing once every screen has been closed.
+    pub fn frame(&mut self, gs: &mut State) {
+        let Some(top) = self.screens.last().cloned() else {
+            return;
+        };
+
+        // The game waits, rather than being played blind, until there is room for it
+        if gui::fits_screen(self.get_terminal_size()) {
+            top.update(gs, self);
+
+            if self.screens.is_empty() {
+                return;
+            }
+
+            // Everything under the topmost full screen is hidden anyway
+            let first = self
+                .screens
+                .iter()
+                .rposition(|screen| !screen.is_overlay())
+                .unwrap_or(0);
+            let visible = self.screens[first..].to_vec();
+
+            for screen in visible {
+                screen.draw(gs, self);
+            }
+        } else {
+            // Keys pressed at the message would otherwise all be played once it is gone
+            self.input_handler.clear_keys();
+            gui::draw_too_small(self);
+        }
+
+        self.cam.render();
+    }
+
     pub fn set(&mut self, pos: &Position, renderable: &Renderable) {
         self.cam.buffer.push((*pos, *renderable));
     }
 
+    /// Tints the background of the world tile at `pos` for this frame, leaving its glyph as is.
+    pub fn highlight(&mut self, pos: &Position, bg: Color) {
+        self.cam.highlights.push((*pos, bg));
+    }
+
+    /// Writes `text` in screen space, starting at column `x` of row `y` (top-left origin).
+    pub fn print(&mut self, x: isize, y: isize, text: &str, fg: Color) {
+        self.print_styled(x, y, text, fg, Attributes::NONE);
+    }
+
+    /// Prints `text` like `print`, drawn with `attrs`.
+    pub fn print_styled(&mut self, x: isize, y: isize, text: &str, fg: Color, attrs: Attributes) {
+        for (i, glyph) in text.chars().enumerate() {
+            let pos = Position { x: x + i as isize, y };
+            let renderable = Renderable {
+                glyph,
+                fg,
+                bg: Color::Default,
+                layer: Layer::Ui,
+                attrs,
+            };
+
+            self.cam.ui_buffer.push((pos, renderable));
+        }
+    }
+
     pub fn cls(&self) {
         print!("{esc}c", esc = 27 as char);
     }
 
-    pub fn get_terminal_size() -> (u16, u16) {
-        terminal::size().unwrap()
+    /// Returns the width and height of the screen in cells.
+    pub fn get_terminal_size(&self) -> (u16, u16) {
+        self.cam.size()
     }
 
+    /// Returns the width and height of the part of the screen the world is drawn in.
+    pub fn get_viewport_size(&self) -> (u16, u16) {
+        self.cam.viewport()
+    }
+
+    /// Returns whether the player asked to quit right away, with `Ctrl-C`.
     pub fn should_stop(&mut self) -> bool {
-        self.input_handler
-            .get_key_once(&crossterm::event::KeyCode::Char('q'))
+        self.input_handler.take_first(|key| key.is_ctrl('c')).is_some()
+    }
+
+    /// Returns whether the player asked to suspend the game, with `Ctrl-Z`.
+    ///
+    /// The terminal doesn't turn `Ctrl-Z` into a signal while the game has it in raw mode,
+    /// so the game has to notice the key itself.
+    pub fn should_suspend(&mut self) -> bool {
+        self.input_handler.take_first(|key| key.is_ctrl('z')).is_some()
+    }
+
+    /// Shows `screen` on top of the current one.
+    pub fn push_screen(&mut self, screen: Screen) {
+        self.screens.push(screen);
+    }
+
+    /// Closes the top screen, going back to the one beneath it.
+    pub fn pop_screen(&mut self) {
+        self.screens.pop();
+    }
+
+    /// Swaps the top screen for `screen`.
+    pub fn replace_screen(&mut self, screen: Screen) {
+        self.screens.pop();
+        self.screens.push(screen);
+    }
+
+    /// Closes every screen and shows `screen` on its own.
+    pub fn reset_screens(&mut self, screen: Screen) {
+        self.screens = vec![screen];
+    }
+
+    /// Closes every screen, which ends the game at the end of the frame.
+    pub fn quit(&mut self) {
+        self.screens.clear();
     }
 
     pub fn set_cam_pos(&mut self, pos: Position) {
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{
+    components::{off_floor::OffFloor, position::Position, viewshed::Viewshed},
+    map::{Map, Tile},
+    map_builders::{BuilderSchedule, BuiltMap, MapError},
+    player::Player,
+    spawner,
+    systems::map_indexing_system::MapIndexingSystem,
+    utils::rectangle::Rectangle,
+};
+
+/// The deepest floor of the dungeon. Taking its down stairs leaves the dungeon and wins
+/// the run.
+pub const FINAL_DEPTH: usize = 10;
+
+/// Every floor of the current run.
+///
+/// The floor being played lives in the `Map` resource. Floors the player has left are
+/// kept here, so their layout and revealed tiles are still there when they come back.
+#[derive(Serialize, Deserialize)]
+pub struct Dungeon {
+    /// The seed of the whole run. Each floor derives its own seed from it.
+    pub seed: u64,
+    builders: BuilderSchedule,
+    /// Whether diagonal steps may squeeze past wall corners on every floor.
+    cut_corners: bool,
+    floors: HashMap<usize, Map>,
+}
+
+/// Which way the player is taking the stairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloorChange {
+    Descend,
+    Ascend,
+}
+
+impl Dungeon {
+    pub fn new(seed: u64, builders: BuilderSchedule) -> Self {
+        Dungeon {
+            seed,
+            builders,
+            cut_corners: false,
+            floors: HashMap::new(),
+        }
+    }
+
+    /// Sets whether diagonal steps may squeeze past wall corners on the floors of this run.
+    pub fn with_corner_cutting(mut self, cut_corners: bool) -> Self {
+        self.cut_corners = cut_corners;
+        self
+    }
+
+    /// Returns the deepest floor the player has left behind, or 0 if they never left one.
+    pub fn deepest_floor(&self) -> usize {
+        self.floors.keys().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the seed for floor number `depth`.
+    ///
+    /// The first floor uses the run seed as is, so it matches a single floor generated
+    /// from the same seed.
+    pub fn floor_seed(&self, depth: usize) -> u64 {
+        self.seed
+            .wrapping_add((depth.saturating_sub(1) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    /// Generates floor number `depth` of this run.
+    pub fn generate_floor(&self, depth: usize) -> Result<BuiltMap, MapError> {
+        let kind = self.builders.for_depth(depth);
+        let mut built = Map::new_dungeon_floor(self.floor_seed(depth), kind, depth)?;
+        built.map.cut_corners = self.cut_corners;
+
+        Ok(built)
+    }
+
+    /// Returns floor number `depth`, generating it if it was never visited.
+    ///
+    /// Freshly generated floors also come with the rooms they were built with, so they
+    /// can be populated.
+    fn take_or_generate(&mut self, depth: usize) -> Result<(Map, Option<Vec<Rectangle>>), MapError> {
+        match self.floors.remove(&depth) {
+            Some(map) => Ok((map, None)),
+            None => {
+                let built = self.generate_floor(depth)?;
+                Ok((built.map, Some(built.rooms)))
+            }
+        }
+    }
+}
+
+/// Moves the player one floor up or down the stairs they are standing on.
+///
+/// The floor being left is stored in the `Dungeon` and the new floor becomes the `Map`
+/// resource. The player arrives on the matching staircase: the up stairs when going down,
+/// and the down stairs when going up.
+///
+/// Everything else on the floor being left is put aside with an `OffFloor` component and
+/// comes back when the player returns. Floors visited for the first time get populated.
+///
+/// ## Returns
+/// - `Ok(true)` if the player changed floor.
+/// - `Ok(false)` if they are not standing on the right stairs.
+/// - `Err` if the new floor could not be generated. The player stays where they are.
+pub fn change_floor(ecs: &mut World, direction: FloorChange) -> Result<bool, MapError> {
+    let stairs = match direction {
+        FloorChange::Descend => Tile::DownStairs,
+        FloorChange::Ascend => Tile::UpStairs,
+    };
+
+    let (depth, on_stairs) = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let players = ecs.read_storage::<Player>();
+
+        let on_stairs = (&positions, &players)
+            .join()
+            .any(|(pos, _player)| map.get_tile_at(*pos) == Some(&stairs));
+
+        (map.depth, on_stairs)
+    };
+
+    // The final floor's down stairs lead out of the dungeon, not to another floor
+    if !on_stairs || (direction == FloorChange::Descend && depth >= FINAL_DEPTH) {
+        return Ok(false);
+    }
+
+    let target_depth = match direction {
+        FloorChange::Descend => depth + 1,
+        FloorChange::Ascend => depth - 1,
+    };
+
+    let (next, new_rooms) = ecs.fetch_mut::<Dungeon>().take_or_generate(target_depth)?;
+    let arrival = match direction {
+        FloorChange::Descend => next.up_stairs,
+        FloorChange::Ascend => next.down_stairs,
+    };
+
+    let Some(arrival) = arrival else {
+        ecs.fetch_mut::<Dungeon>().floors.insert(target_depth, next);
+        return Ok(false);
+    };
+
+    let previous = std::mem::replace(&mut *ecs.fetch_mut::<Map>(), next);
+    ecs.fetch_mut::<Dungeon>().floors.insert(depth, previous);
+
+    swap_floor_entities(ecs, depth, target_depth, arrival);
+
+    if let Some(rooms) = new_rooms {
+        spawner::spawn_floor(ecs, &rooms, arrival);
+    }
+
+    // A stored floor still has the indexes from when it was left, if it has any at all
+    MapIndexingSystem {}.run_now(ecs);
+
+    Ok(true)
+}
+
+/// Returns whether the player is standing on the down stairs of the final floor, which
+/// lead out of the dungeon.
+pub fn is_on_exit(ecs: &World) -> bool {
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let players = ecs.read_storage::<Player>();
+
+    map.depth >= FINAL_DEPTH
+        && (&positions, &players)
+            .join()
+            .any(|(pos, _player)| map.get_tile_at(*pos) == Some(&Tile::DownStairs))
+}
+
+/// Puts aside every entity on floor `from` and brings back the ones left on floor `to`.
+/// The player is moved to `arrival`.
+fn swap_floor_entities(ecs: &mut World, from: usize, to: usize, arrival: Position) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut off_floor = ecs.write_storage::<OffFloor>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let players = ecs.read_storage::<Player>();
+
+    let leaving: Vec<(Entity, Position)> = (&entities, &positions, !&players)
+        .join()
+        .map(|(entity, pos, _)| (entity, *pos))
+        .collect();
+
+    let returning: Vec<(Entity, Position)> = (&entities, &off_floor)
+        .join()
+        .filter(|(_, off)| off.depth == to)
+        .map(|(entity, off)| (entity, off.pos))
+        .collect();
+
+    for (entity, pos) in leaving {
+        positions.remove(entity);
+        off_floor
+            .insert(entity, OffFloor { depth: from, pos })
+            .expect("Unable to put entity aside");
+    }
+
+    for (entity, pos) in returning {
+        off_floor.remove(entity);
+        positions
+            .insert(entity, pos)
+            .expect("Unable to bring entity back");
+    }
+
+    for (pos, _player) in (&mut positions, &players).join() {
+        *pos = arrival;
+    }
+
+    // Every viewshed was computed against the floor that was just left
+    for viewshed in (&mut viewsheds).join() {
+        viewshed.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::monster::Monster, map_builders::BuilderKind, utils::settings::Settings};
+
+    fn started_run() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        crate::new_game(&mut ecs, &Settings::default(), 8, BuilderSchedule::default()).unwrap();
+
+        ecs
+    }
+
+    fn player_position(ecs: &World) -> Position {
+        let positions = ecs.read_storage::<Position>();
+        let players = ecs.read_storage::<Player>();
+
+        (&positions, &players).join().next().map(|(pos, _player)| *pos).unwrap()
+    }
+
+    fn move_player(ecs: &mut World, to: Position) {
+        let players = ecs.read_storage::<Player>();
+        for (pos, _player) in (&mut ecs.write_storage::<Position>(), &players).join() {
+            *pos = to;
+        }
+    }
+
+    /// Puts the player on the down stairs of the current floor and takes them.
+    fn descend(ecs: &mut World) -> Position {
+        let stairs = ecs.fetch::<Map>().down_stairs.unwrap();
+        move_player(ecs, stairs);
+        assert!(matches!(change_floor(ecs, FloorChange::Descend), Ok(true)));
+
+        stairs
+    }
+
+    fn monsters(ecs: &World) -> Vec<(Entity, Option<Position>, Option<usize>)> {
+        let entities = ecs.entities();
+        let monsters = ecs.read_storage::<Monster>();
+        let positions = ecs.read_storage::<Position>();
+        let off_floor = ecs.read_storage::<OffFloor>();
+
+        (&entities, &monsters)
+            .join()
+            .map(|(entity, _monster)| {
+                let depth = off_floor.get(entity).map(|off| off.depth);
+                (entity, positions.get(entity).copied(), depth)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn each_floor_is_built_with_its_scheduled_builder() {
+        let schedule = BuilderSchedule::every_floor(Some(BuilderKind::Bsp))
+            .with_floor(2, BuilderKind::DrunkardsWalk);
+        let dungeon = Dungeon::new(21, schedule);
+
+        let expected = [
+            (1, BuilderKind::Bsp),
+            (2, BuilderKind::DrunkardsWalk),
+            (3, BuilderKind::Bsp),
+        ];
+        for (depth, kind) in expected {
+            let scheduled = dungeon.generate_floor(depth).unwrap();
+            let seed = dungeon.floor_seed(depth);
+            let forced = Map::new_dungeon_floor(seed, Some(kind), depth).unwrap();
+
+            assert_eq!(
+                serde_json::to_string(&scheduled.map).unwrap(),
+                serde_json::to_string(&forced.map).unwrap(),
+                "floor {depth}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_stairs_lead_back_to_where_they_were_taken() {
+        let mut ecs = started_run();
+
+        let down = descend(&mut ecs);
+        assert_eq!(ecs.fetch::<Map>().depth, 2);
+        assert_eq!(Some(player_position(&ecs)), ecs.fetch::<Map>().up_stairs);
+
+        assert!(matches!(change_floor(&mut ecs, FloorChange::Ascend), Ok(true)));
+        assert_eq!(ecs.fetch::<Map>().depth, 1);
+        assert_eq!(player_position(&ecs), down);
+    }
+
+    #[test]
+    fn a_stored_floor_keeps_its_revealed_tiles() {
+        let mut ecs = started_run();
+        let far_corner = {
+            let mut map = ecs.fetch_mut::<Map>();
+            let corner = Position { x: map.width - 1, y: map.height - 1 };
+            map.reveal_tile(corner);
+            corner
+        };
+
+        descend(&mut ecs);
+        assert!(!ecs.fetch::<Map>().is_revealed(far_corner));
+        change_floor(&mut ecs, FloorChange::Ascend).unwrap();
+
+        assert!(ecs.fetch::<Map>().is_revealed(far_corner));
+    }
+
+    #[test]
+    fn monsters_wait_on_their_floor() {
+        let mut ecs = started_run();
+        let first_floor = monsters(&ecs);
+        assert!(!first_floor.is_empty());
+
+        descend(&mut ecs);
+        for (entity, pos, _) in &first_floor {
+            let off = ecs.read_storage::<OffFloor>().get(*entity).copied();
+            assert!(off.is_some_and(|off| off.depth == 1 && Some(off.pos) == *pos));
+            assert!(!ecs.read_storage::<Position>().contains(*entity));
+        }
+
+        change_floor(&mut ecs, FloorChange::Ascend).unwrap();
+        let back: Vec<_> = monsters(&ecs)
+            .into_iter()
+            .filter(|(_, _, depth)| *depth != Some(2))
+            .collect();
+
+        assert_eq!(back, first_floor);
+    }
+
+    #[test]
+    fn nothing_happens_away_from_the_stairs() {
+        let mut ecs = started_run();
+        let spawn = player_position(&ecs);
+        assert_eq!(ecs.fetch::<Map>().get_tile_at(spawn), Some(&Tile::Floor));
+
+        assert!(matches!(change_floor(&mut ecs, FloorChange::Descend), Ok(false)));
+        assert!(matches!(change_floor(&mut ecs, FloorChange::Ascend), Ok(false)));
+        assert_eq!(ecs.fetch::<Map>().depth, 1);
+        assert_eq!(player_position(&ecs), spawn);
+        assert_eq!(ecs.fetch::<Dungeon>().deepest_floor(), 0);
+    }
+}
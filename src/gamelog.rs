@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// How many messages are kept before the oldest ones are dropped.
+const MAX_ENTRIES: usize = 200;
+
+/// Messages about what happened in the game, oldest first. Stored as a resource in the
+/// `World` and shown in the side panel.
+#[derive(Default, Serialize, Deserialize)]
+pub struct GameLog {
+    pub entries: Vec<String>,
+}
+
+impl GameLog {
+    pub fn new() -> Self {
+        GameLog { entries: vec![] }
+    }
+
+    /// Adds `message` to the end of the log.
+    pub fn log(&mut self, message: impl Into<String>) {
+        self.entries.push(message.into());
+
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use bracket_lib::prelude::{field_of_view, Point};
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        area_of_effect::AreaOfEffect, combat_stats::CombatStats, defense_bonus::DefenseBonus,
+        equippable::EquipmentSlot, equipped::Equipped, in_backpack::InBackpack,
+        melee_power_bonus::MeleePowerBonus, name::Name, position::Position, ranged::Ranged,
+    },
+    ctx::Ctx,
+    dungeon::Dungeon,
+    gamelog::GameLog,
+    map::Map,
+    player::{self, Player},
+    systems::melee_combat_system::{defense_bonus, power_bonus},
+    utils::{attributes::Attributes, color::Color},
+    TurnCount,
+};
+
+/// The width of the side panel, including its border.
+pub const PANEL_WIDTH: u16 = 30;
+/// The gap between the border and the text of the panel.
+const PADDING: u16 = 2;
+/// The smallest screen the game is drawn on: room for the panel, a useful view of the
+/// world, and the menus over it.
+pub const MIN_SCREEN_SIZE: (u16, u16) = (80, 24);
+
+/// How the screen is split between the world and the side panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// The size of the world view, in the top-left corner of the screen.
+    pub viewport: (u16, u16),
+    /// The column the panel's border is drawn in.
+    pub panel_x: u16,
+    pub height: u16,
+}
+
+impl Layout {
+    /// Splits a screen of `screen_size` cells, giving the panel its width on the right.
+    pub fn new(screen_size: (u16, u16)) -> Self {
+        let panel_width = PANEL_WIDTH.min(screen_size.0);
+        let panel_x = screen_size.0 - panel_width;
+
+        Layout {
+            viewport: (panel_x, screen_size.1),
+            panel_x,
+            height: screen_size.1,
+        }
+    }
+
+    /// How many columns of text fit in the panel.
+    fn text_width(&self) -> usize {
+        PANEL_WIDTH.saturating_sub(PADDING + 1) as usize
+    }
+}
+
+/// Returns whether a screen of `screen_size` cells is big enough for the game.
+pub fn fits_screen(screen_size: (u16, u16)) -> bool {
+    screen_size.0 >= MIN_SCREEN_SIZE.0 && screen_size.1 >= MIN_SCREEN_SIZE.1
+}
+
+/// Draws the message shown instead of the game while the screen is too small for it.
+pub fn draw_too_small(ctx: &mut Ctx) {
+    let (width, height) = ctx.get_terminal_size();
+    let text = format!(
+        "The terminal is too small: {width}x{height}. The game needs at least {}x{}, \
+         make the window bigger to continue.",
+        MIN_SCREEN_SIZE.0, MIN_SCREEN_SIZE.1
+    );
+
+    let lines = wrap(&text, width.max(1) as usize);
+    let top = (height as isize - lines.len() as isize).max(0) / 2;
+
+    for (i, line) in lines.iter().enumerate() {
+        let x = (width as isize - line.chars().count() as isize) / 2;
+        ctx.print(x, top + i as isize, line, Color::Yellow);
+    }
+}
+
+/// Draws the side panel: the player's health and combat strength, where they are, and
+/// the message log.
+pub fn draw_ui(ecs: &World, ctx: &mut Ctx, layout: &Layout) {
+    let x = (layout.panel_x + PADDING) as isize;
+    let mut y: isize = 1;
+
+    for row in 0..layout.height as isize {
+        ctx.print(layout.panel_x as isize, row, "│", Color::White);
+    }
+
+    let entities = ecs.entities();
+    let players = ecs.read_storage::<Player>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let names = ecs.read_storage::<Name>();
+
+    if let Some((player, _player, stats, name)) =
+        (&entities, &players, &combat_stats, &names).join().next()
+    {
+        ctx.print(x, y, &name.name, Color::Yellow);
+        y += 1;
+
+        ctx.print(
+            x,
+            y,
+            &format!("HP: {} / {}", stats.hp.max(0), stats.max_hp),
+            Color::White,
+        );
+        y += 1;
+
+        let bar_width = layout.text_width();
+        // Nothing stops hp from going over max_hp, so the bar can't trust it to fit
+        let filled = ((stats.hp.max(0) as usize * bar_width) / stats.max_hp.max(1) as usize)
+            .min(bar_width);
+        let bar_color = match stats.hp * 4 / stats.max_hp.max(1) {
+            3.. => Color::Green,
+            1..=2 => Color::Yellow,
+            _ => Color::Red,
+        };
+        ctx.print(x, y, &"█".repeat(filled), bar_color);
+        ctx.print(
+            x + filled as isize,
+            y,
+            &"░".repeat(bar_width.saturating_sub(filled)),
+            bar_color,
+        );
+        y += 1;
+
+        // What the player hits and blocks with, counting everything they have equipped
+        let equipped = ecs.read_storage::<Equipped>();
+        let power =
+            stats.power + power_bonus(player, &equipped, &ecs.read_storage::<MeleePowerBonus>());
+        let defense =
+            stats.defense + defense_bonus(player, &equipped, &ecs.read_storage::<DefenseBonus>());
+        ctx.print(x, y, &format!("Power: {power}  Defense: {defense}"), Color::White);
+        y += 2;
+    }
+
+    let depth = ecs.fetch::<Map>().depth;
+    let turn = ecs.fetch::<TurnCount>().0;
+    let seed = ecs.fetch::<Dungeon>().seed;
+
+    for line in [
+        format!("Depth: {depth}"),
+        format!("Turn: {turn}"),
+        format!("Seed: {seed}"),
+    ] {
+        ctx.print(x, y, &line, Color::White);
+        y += 1;
+    }
+    y += 1;
+
+    ctx.print(x, y, "Messages", Color::Yellow);
+    y += 1;
+
+    // The newest messages sit at the bottom, older ones scroll off the top
+    let log = ecs.fetch::<GameLog>();
+    let rows = (layout.height as isize - y).max(0) as usize;
+    let lines: Vec<String> = log
+        .entries
+        .iter()
+        .flat_map(|entry| wrap(entry, layout.text_width()))
+        .collect();
+
+    for line in &lines[lines.len().saturating_sub(rows)..] {
+        ctx.print(x, y, line, Color::White);
+        y += 1;
+    }
+}
+
+/// Returns the items the player is carrying, in the order the item menus list them.
+pub fn backpack_items(ecs: &World) -> Vec<Entity> {
+    let entities = ecs.entities();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let players = ecs.read_storage::<Player>();
+
+    (&entities, &backpack)
+        .join()
+        .filter(|(_, carried)| players.contains(carried.owner))
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Draws a box over the middle of the world view listing the items the player carries,
+/// each under the letter that picks it. The pack never holds more than
+/// `BACKPACK_CAPACITY` items, so there is a letter for every one of them.
+pub fn draw_item_menu(ecs: &World, ctx: &mut Ctx, layout: &Layout, title: &str) {
+    let names = ecs.read_storage::<Name>();
+
+    let mut lines: Vec<String> = backpack_items(ecs)
+        .into_iter()
+        .zip('a'..='z')
+        .map(|(item, letter)| {
+            let name = names.get(item).map_or("something", |n| n.name.as_str());
+            format!("({letter}) {name}")
+        })
+        .collect();
+
+    if lines.is_empty() {
+        lines.push("You aren't carrying anything.".to_string());
+    }
+
+    draw_menu(ctx, layout.viewport, title, "Esc to cancel", &lines, None);
+}
+
+// Every slot needs a letter of its own on the equipment screen
+const _: () = assert!(EquipmentSlot::ALL.len() <= 26);
+
+/// Draws a box over the middle of the world view listing what the player has equipped
+/// in each slot, under the letter that takes it off.
+pub fn draw_equipment(ecs: &World, ctx: &mut Ctx, layout: &Layout) {
+    let names = ecs.read_storage::<Name>();
+
+    let lines: Vec<String> = EquipmentSlot::ALL
+        .into_iter()
+        .zip('a'..='z')
+        .map(|(slot, letter)| match player::equipped_item(ecs, slot) {
+            Some(item) => {
+                let name = names.get(item).map_or("something", |n| n.name.as_str());
+                format!("({letter}) {}: {name}", slot.name())
+            }
+            None => format!("    {}: -", slot.name()),
+        })
+        .collect();
+
+    draw_menu(
+        ctx,
+        layout.viewport,
+        "Equipment",
+        "Letter to take off, Esc to close",
+        &lines,
+        None,
+    );
+}
+
+/// Draws a box with `title` and `footer` in its border over the middle of the top-left
+/// `area` of the screen, with `lines` inside it. The line at index `selected`, if any, is
+/// marked as the one picked.
+///
+/// ## Returns
+/// The row the bottom of the box is drawn on.
+pub fn draw_menu(
+    ctx: &mut Ctx,
+    area: (u16, u16),
+    title: &str,
+    footer: &str,
+    lines: &[String],
+    selected: Option<usize>,
+) -> isize {
+    // Menus with a pick leave room to mark it
+    let lines: Vec<String> = match selected {
+        None => lines.to_vec(),
+        Some(selected) => lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| format!("{} {line}", if i == selected { '>' } else { ' ' }))
+            .collect(),
+    };
+
+    let inner_width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .chain([title.chars().count(), footer.chars().count()])
+        .max()
+        .unwrap_or(0)
+        + 2;
+
+    let width = inner_width as isize + 2;
+    let height = lines.len() as isize + 2;
+    let x = (area.0 as isize - width) / 2;
+    let y = (area.1 as isize - height) / 2;
+
+    let edge = |label: &str, left: char, right: char| {
+        let fill = "─".repeat(inner_width - label.chars().count() - 2);
+        format!("{left} {label} {fill}{right}")
+    };
+
+    ctx.print(x, y, &edge(title, '┌', '┐'), Color::Yellow);
+    for (i, line) in lines.iter().enumerate() {
+        let row = y + 1 + i as isize;
+        let padding = " ".repeat(inner_width - line.chars().count() - 1);
+
+        if selected == Some(i) {
+            ctx.print(x, row, &format!("│ {}│", " ".repeat(inner_width - 1)), Color::Yellow);
+            ctx.print_styled(x + 2, row, line, Color::Yellow, Attributes::REVERSE);
+        } else {
+            ctx.print(x, row, &format!("│ {line}{padding}│"), Color::White);
+        }
+    }
+    // The footer only says which keys to press, so it is set apart from the options
+    ctx.print_styled(
+        x,
+        y + height - 1,
+        &edge(footer, '└', '┘'),
+        Color::Yellow,
+        Attributes::ITALIC,
+    );
+
+    y + height - 1
+}
+
+/// Draws the aiming overlay for the ranged `item`: the tiles it can reach, the cursor,
+/// and for items with an area of effect, the tiles the blast would hit.
+pub fn draw_targeting(ecs: &World, ctx: &mut Ctx, layout: &Layout, item: Entity, cursor: Position) {
+    let range = ecs.read_storage::<Ranged>().get(item).map_or(0, |r| r.range);
+
+    for tile in player::targetable_tiles(ecs, range) {
+        ctx.highlight(&tile, Color::Blue);
+    }
+
+    if let Some(area) = ecs.read_storage::<AreaOfEffect>().get(item) {
+        let map = ecs.fetch::<Map>();
+
+        for p in field_of_view(Point::new(cursor.x, cursor.y), area.radius, &*map) {
+            ctx.highlight(&Position { x: p.x as isize, y: p.y as isize }, Color::Red);
+        }
+    }
+
+    ctx.highlight(&cursor, Color::Cyan);
+
+    let name = ecs
+        .read_storage::<Name>()
+        .get(item)
+        .map_or("item".to_string(), |n| n.name.clone());
+    let prompt = format!("Aim the {name}: Enter to use, Esc to cancel");
+    let x = (layout.viewport.0 as isize - prompt.chars().count() as isize) / 2;
+    ctx.print(x, 0, &prompt, Color::Yellow);
+}
+
+/// Breaks `text` into lines of at most `width` characters, splitting between words
+/// where it can.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+
+        if !line.is_empty() && line.chars().count() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        // Words longer than a whole line get cut up
+        while word.len() > width.max(1) {
+            let rest = word.split_off(width.max(1));
+            lines.push(word.into_iter().collect());
+            word = rest;
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
@@ -1,109 +1,718 @@
-use camera::Camera;
-use components::{position::Position, renderable::Renderable};
+use components::{
+    area_of_effect::AreaOfEffect, blocks_tile::BlocksTile, combat_stats::CombatStats,
+    confusion::Confusion, consumable::Consumable, defense_bonus::DefenseBonus, energy::Energy,
+    equippable::Equippable, equipped::Equipped, in_backpack::InBackpack,
+    inflicts_damage::InflictsDamage, item::Item, melee_power_bonus::MeleePowerBonus,
+    monster::Monster, name::Name, off_floor::OffFloor, position::Position, provides_healing::ProvidesHealing,
+    ranged::Ranged, renderable::{Layer, Renderable},
+    suffer_damage::SufferDamage, viewshed::Viewshed, wants_to_drop_item::WantsToDropItem,
+    wants_to_melee::WantsToMelee, wants_to_pickup_item::WantsToPickupItem,
+    wants_to_equip_item::WantsToEquipItem, wants_to_unequip_item::WantsToUnequipItem,
+    wants_to_use_item::WantsToUseItem,
+};
 use ctx::Ctx;
+use dungeon::Dungeon;
+use gamelog::GameLog;
 use map::Map;
+use map_builders::{BuilderSchedule, MapError};
 use player::Player;
-use specs::prelude::*;
-use specs_derive::Component;
+use saveload::{SaveError, SerializeMe};
+use serde::{Deserialize, Serialize};
+use specs::{
+    prelude::*,
+    saveload::{MarkedBuilder, SimpleMarker, SimpleMarkerAllocator},
+};
+use screen::Screen;
+use std::path::PathBuf;
+use systems::{
+    damage_system::DamageSystem, energy_system,
+    item_collection_system::ItemCollectionSystem, item_drop_system::ItemDropSystem,
+    item_equip_system::ItemEquipSystem,
+    item_use_system::ItemUseSystem, map_indexing_system::MapIndexingSystem, melee_combat_system::MeleeCombatSystem,
+    monster_ai_system::MonsterAI, visibility_system::VisibilitySystem,
+};
+use terminal::TerminalGuard;
+use utils::settings::Settings;
 
 pub mod camera;
 pub mod components;
 pub mod ctx;
+pub mod dungeon;
+pub mod gamelog;
+pub mod gui;
 pub mod map;
+pub mod map_builders;
 pub mod player;
+pub mod render_target;
+pub mod saveload;
+pub mod screen;
+pub mod spawner;
+pub mod systems;
+pub mod terminal;
 pub mod utils;
 
 pub trait GameState {
+    /// Handles the player's input and moves the game on as far as it goes without them.
     fn tick(&mut self, ctx: &mut Ctx);
+    /// Draws the game as it is now.
+    fn draw(&self, ctx: &mut Ctx);
 }
 
+/// Where the game is in the turn cycle. Stored as a resource in the `World`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    /// Settles the world before the first turn.
+    PreRun,
+    /// Waiting for the player to do something. Nothing else moves.
+    AwaitingInput,
+    /// The player just acted, the world reacts to it.
+    PlayerTurn,
+    /// Game time moves forward until the player can act again, letting monsters act
+    /// whenever they have the energy.
+    MonsterTurn,
+    /// The inventory is open. Picking an item uses it.
+    ShowInventory,
+    /// The inventory is open. Picking an item drops it.
+    ShowDropItem,
+    /// The equipment screen is open. Picking a slot takes off what is worn there.
+    ShowEquipment,
+    /// The player is picking the tile to aim the ranged `item` at, with the cursor on `cursor`.
+    ShowTargeting { item: Entity, cursor: Position },
+    /// The player died. Nothing moves any more.
+    GameOver,
+    /// The player took the stairs out of the final floor. Nothing moves any more.
+    Victory,
+}
+
+/// How many turns the player has taken. Stored as a resource in the `World`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnCount(pub u64);
+
+/// How many monsters have died this run. Stored as a resource in the `World`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KillCount(pub u64);
+
 pub struct State {
     ecs: World,
+    /// Where the run is saved, if the platform has anywhere to save it.
+    save_path: Option<PathBuf>,
+    /// The seed every new run starts from, if one was forced with `--seed`.
+    seed: Option<u64>,
+    /// The layout algorithms floors are generated with, as forced with `--builder`.
+    builders: BuilderSchedule,
 }
 
 impl State {
-    fn run_systems(&mut self) {
-        let mut lw = LeftWalker {};
-        lw.run_now(&self.ecs);
+    /// Returns whether a run is loaded that can still be played: the player is in the
+    /// world and has neither died nor won.
+    pub fn has_unfinished_run(&self) -> bool {
+        let has_player = self.ecs.read_storage::<Player>().join().next().is_some();
+
+        has_player && !player::is_dead(&self.ecs) && !player::has_won(&self.ecs)
+    }
+
+    /// Throws away whatever run is loaded and starts a fresh one.
+    fn new_game(&mut self, settings: &Settings) -> Result<(), MapError> {
+        self.clear();
+        let seed = self.seed.unwrap_or_else(rand::random);
+        new_game(&mut self.ecs, settings, seed, self.builders.clone())
+    }
+
+    /// Goes back to the run that is still loaded or, if there is none, to the one in the
+    /// save file.
+    fn continue_game(&mut self) -> Result<(), SaveError> {
+        if self.has_unfinished_run() {
+            return Ok(());
+        }
+
+        let Some(path) = self.save_path.clone() else {
+            return Ok(());
+        };
+
+        saveload::load_game(&mut self.ecs, &path)
+    }
+
+    /// Brings the save file up to date with the loaded run.
+    ///
+    /// A finished run has nothing left to resume, so its save is deleted instead. With no
+    /// run loaded, the save file is left alone.
+    fn save(&self) -> Result<(), SaveError> {
+        let has_player = self.ecs.read_storage::<Player>().join().next().is_some();
+
+        match &self.save_path {
+            Some(path) if has_player && !self.has_unfinished_run() => saveload::delete_save(path),
+            Some(path) if has_player => saveload::save_game(&self.ecs, path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes every entity, leaving no run loaded.
+    fn clear(&mut self) {
+        self.ecs.delete_all();
         self.ecs.maintain();
     }
+
+    fn run_systems(&mut self) {
+        run_systems(&mut self.ecs);
+    }
+
+    /// Runs the energy scheduler until it is the player's turn again, or until the
+    /// player dies.
+    fn run_monster_turn(&mut self) {
+        energy_system::run_until_player_acts(&mut self.ecs, |ecs| {
+            let mut mob = MonsterAI {};
+            mob.run_now(ecs);
+            run_systems(ecs);
+
+            !player::is_dead(ecs)
+        });
+    }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut Ctx) {
-        // ctx.cls();
-        {
-            let map = self.ecs.fetch::<Map>();
-            map.draw_map(ctx);
+        let mut runstate = *self.ecs.fetch::<RunState>();
+        let previous = runstate;
+
+        // Keep going through the turn until it needs the player again
+        loop {
+            runstate = match runstate {
+                RunState::PreRun => {
+                    self.run_systems();
+                    RunState::AwaitingInput
+                }
+                RunState::AwaitingInput => {
+                    let next = player::player_input(self, ctx);
+                    if next == RunState::AwaitingInput {
+                        break;
+                    }
+                    next
+                }
+                RunState::PlayerTurn => {
+                    player::spend_turn(&mut self.ecs);
+                    self.run_systems();
+                    RunState::MonsterTurn
+                }
+                RunState::MonsterTurn => {
+                    self.run_monster_turn();
+                    RunState::AwaitingInput
+                }
+                RunState::ShowInventory | RunState::ShowDropItem => {
+                    let next = player::item_menu_input(self, ctx, runstate);
+                    if next == runstate {
+                        break;
+                    }
+                    next
+                }
+                RunState::ShowEquipment => {
+                    let next = player::equipment_input(self, ctx);
+                    if next == runstate {
+                        break;
+                    }
+                    next
+                }
+                RunState::ShowTargeting { item, cursor } => {
+                    let next = player::targeting_input(self, ctx, item, cursor);
+                    if next == runstate {
+                        break;
+                    }
+                    next
+                }
+                RunState::GameOver | RunState::Victory => break,
+            };
+
+            if player::is_dead(&self.ecs) {
+                runstate = RunState::GameOver;
+            }
         }
 
-        player::player_input(self, ctx);
-        self.run_systems();
+        *self.ecs.write_resource::<RunState>() = runstate;
 
-        if let Some(pos) = player::get_camera_pos(self) {
+        if runstate != previous && matches!(runstate, RunState::GameOver | RunState::Victory) {
+            // The save is gone as soon as the run ends, so it can't be continued from
+            if let Err(error) = self.save() {
+                self.ecs
+                    .write_resource::<GameLog>()
+                    .log(format!("Could not delete the save: {error}"));
+            }
+
+            ctx.push_screen(Screen::RunOver {
+                won: runstate == RunState::Victory,
+            });
+        }
+    }
+
+    fn draw(&self, ctx: &mut Ctx) {
+        let runstate = *self.ecs.fetch::<RunState>();
+
+        // The world gets whatever the side panel leaves over
+        let layout = gui::Layout::new(ctx.get_terminal_size());
+        ctx.cam.set_viewport(layout.viewport);
+
+        if let Some(pos) = player::get_camera_pos(self, ctx) {
             ctx.set_cam_pos(pos);
         }
 
         let positions = self.ecs.read_storage::<Position>();
         let renderables = self.ecs.read_storage::<Renderable>();
-        let players = self.ecs.read_storage::<Player>();
 
-        for (pos, _player) in (&positions, &players).join() {
-            let mut map = self.ecs.fetch_mut::<Map>();
-            map.reveal_fov(*pos);
+        {
+            // The map only draws what the camera can see, so it has to follow the camera move
+            let map = self.ecs.fetch::<Map>();
+            map.draw_map(ctx);
+
+            // Entities are only drawn while they are in view
+            for (pos, render) in (&positions, &renderables).join() {
+                if map.is_visible(*pos) {
+                    ctx.set(pos, render);
+                }
+            }
         }
 
-        for (pos, render) in (&positions, &renderables).join() {
-            ctx.set(pos, render);
+        gui::draw_ui(&self.ecs, ctx, &layout);
+
+        match runstate {
+            RunState::ShowInventory => gui::draw_item_menu(&self.ecs, ctx, &layout, "Use which item?"),
+            RunState::ShowDropItem => gui::draw_item_menu(&self.ecs, ctx, &layout, "Drop which item?"),
+            RunState::ShowEquipment => gui::draw_equipment(&self.ecs, ctx, &layout),
+            RunState::ShowTargeting { item, cursor } => {
+                gui::draw_targeting(&self.ecs, ctx, &layout, item, cursor)
+            }
+            _ => {}
         }
     }
 }
 
-#[derive(Component)]
-struct LeftMover {}
+fn main() {
+    let mut gs: State = State {
+        ecs: World::new(),
+        save_path: saveload::save_path(),
+        seed: utils::args::get_seed().unwrap_or_else(|error| {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }),
+        builders: utils::args::get_builder_schedule().unwrap_or_else(|error| {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }),
+    };
 
-#[derive(Component)]
-struct LeftWalker {}
+    let mut context: Ctx = Ctx::new();
 
-impl<'a> System<'a> for LeftWalker {
-    type SystemData = (ReadStorage<'a, LeftMover>, WriteStorage<'a, Position>);
+    register_components(&mut gs.ecs);
 
-    fn run(&mut self, (lefty, mut pos): Self::SystemData) {
-        for (_lefty, pos) in (&lefty, &mut pos).join() {
-            pos.x -= 1;
-            if pos.x < 0 {
-                pos.x = 79;
-            }
+    context.settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(error) => {
+            eprintln!("Could not load the settings: {error}");
+            std::process::exit(1);
         }
+    };
+
+    // `--new-game` skips the title screen, which is still there to quit to
+    let mut screens = vec![Screen::title(&gs, None)];
+    if utils::args::has_flag("--new-game") {
+        if let Err(error) = gs.new_game(&context.settings) {
+            eprintln!("Could not generate a dungeon floor: {error}");
+            std::process::exit(1);
+        }
+        screens.push(Screen::Playing);
+    }
+
+    let guard = TerminalGuard::new();
+    context.main_loop(&mut gs, screens);
+    drop(guard);
+
+    if let Err(error) = gs.save() {
+        eprintln!("Could not save the game: {error}");
+        std::process::exit(1);
     }
 }
 
-fn main() {
-    let mut gs: State = State { ecs: World::new() };
+/// Runs every system that resolves what was queued up this turn, and deletes whatever
+/// died.
+fn run_systems(ecs: &mut World) {
+    let mut vis = VisibilitySystem {};
+    vis.run_now(ecs);
+    let mut pickup = ItemCollectionSystem {};
+    pickup.run_now(ecs);
+    let mut use_items = ItemUseSystem {};
+    use_items.run_now(ecs);
+    let mut drop_items = ItemDropSystem {};
+    drop_items.run_now(ecs);
+    let mut equip = ItemEquipSystem {};
+    equip.run_now(ecs);
+    let mut melee = MeleeCombatSystem {};
+    melee.run_now(ecs);
+    let mut damage = DamageSystem {};
+    damage.run_now(ecs);
+    ecs.maintain();
+    systems::damage_system::delete_the_dead(ecs);
+    ecs.maintain();
+    let mut mapindex = MapIndexingSystem {};
+    mapindex.run_now(ecs);
+}
 
-    let mut context: Ctx = Ctx::new();
+/// Registers every component with `ecs`, along with the allocator for save markers.
+fn register_components(ecs: &mut World) {
+    ecs.register::<Position>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Energy>();
+    ecs.register::<Monster>();
+    ecs.register::<Name>();
+    ecs.register::<BlocksTile>();
+    ecs.register::<OffFloor>();
+    ecs.register::<CombatStats>();
+    ecs.register::<WantsToMelee>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<Item>();
+    ecs.register::<InBackpack>();
+    ecs.register::<Consumable>();
+    ecs.register::<ProvidesHealing>();
+    ecs.register::<Ranged>();
+    ecs.register::<InflictsDamage>();
+    ecs.register::<AreaOfEffect>();
+    ecs.register::<Confusion>();
+    ecs.register::<Equippable>();
+    ecs.register::<Equipped>();
+    ecs.register::<MeleePowerBonus>();
+    ecs.register::<DefenseBonus>();
+    ecs.register::<WantsToPickupItem>();
+    ecs.register::<WantsToUseItem>();
+    ecs.register::<WantsToDropItem>();
+    ecs.register::<WantsToEquipItem>();
+    ecs.register::<WantsToUnequipItem>();
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+}
 
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<LeftMover>();
-    gs.ecs.register::<Player>();
-    
-    let (starting_pos, map) = Map::new_dungeon_floor();
-    gs.ecs
-        .create_entity()
-        .with(starting_pos)
+/// Fills `ecs` with a fresh run from `seed`: the first floor, its monsters and the player.
+/// Each floor is generated with the algorithm `builders` has for it.
+///
+/// ## Returns
+/// `Err` if the first floor could not be generated from the run's seed.
+fn new_game(
+    ecs: &mut World,
+    settings: &Settings,
+    seed: u64,
+    builders: BuilderSchedule,
+) -> Result<(), MapError> {
+    let dungeon = Dungeon::new(seed, builders)
+        .with_corner_cutting(settings.cut_corners);
+    let built = dungeon.generate_floor(1)?;
+    ecs.create_entity()
+        .with(built.spawn)
         .with(Renderable {
             glyph: '@',
             fg: utils::color::Color::Green,
             bg: utils::color::Color::Default,
+            layer: Layer::Actors,
+            attrs: utils::attributes::Attributes::BOLD,
         })
         .with(Player {})
+        .with(Viewshed::new(15))
+        .with(Energy::new(100))
+        .with(CombatStats::new(30, 2, 5))
+        .with(Name {
+            name: "Player".to_string(),
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
-    gs.ecs.insert(map);
+    ecs.insert(built.map);
+    ecs.insert(dungeon);
+    ecs.insert(RunState::PreRun);
+    ecs.insert(TurnCount::default());
+    ecs.insert(KillCount::default());
+
+    let mut log = GameLog::new();
+    log.log("Welcome to the dungeon!");
+    ecs.insert(log);
+
+    spawner::spawn_floor(ecs, &built.rooms, built.spawn);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+    use super::*;
+    use crate::render_target::HeadlessTarget;
+
+    /// Starts a run from `seed` in a world of its own.
+    fn run_from_seed(seed: u64) -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        new_game(&mut ecs, &Settings::default(), seed, BuilderSchedule::default()).unwrap();
+
+        ecs
+    }
+
+    /// Every named entity of the run with where it is, carried items included.
+    fn spawns(ecs: &World) -> Vec<(String, Option<Position>)> {
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
 
-    Camera::load_terminal_settings();
-    context.main_loop(gs);
+        let mut spawns: Vec<_> = (&names, positions.maybe())
+            .join()
+            .map(|(name, pos)| (name.name.clone(), pos.copied()))
+            .collect();
+        spawns.sort_by_key(|(name, pos)| (name.clone(), pos.map(|pos| (pos.x, pos.y))));
 
-    Camera::deload_terminal_settings();
+        spawns
+    }
+
+    #[test]
+    fn same_seed_starts_the_same_run() {
+        let first = run_from_seed(42);
+        let second = run_from_seed(42);
+
+        let tiles = |ecs: &World| serde_json::to_string(&*ecs.fetch::<Map>()).unwrap();
+        assert_eq!(tiles(&first), tiles(&second));
+        assert_eq!(spawns(&first), spawns(&second));
+        assert!(spawns(&first).len() > 1, "the first floor should have more than the player");
+    }
+
+    /// Starts a run from `seed` on a headless `width` x `height` screen, ready to play.
+    fn headless_game(seed: u64, width: u16, height: u16) -> (State, Ctx) {
+        let mut gs = State {
+            ecs: World::new(),
+            save_path: None,
+            seed: Some(seed),
+            builders: BuilderSchedule::default(),
+        };
+        register_components(&mut gs.ecs);
+
+        let mut ctx = Ctx::headless(width, height);
+        gs.new_game(&ctx.settings).unwrap();
+        ctx.push_screen(Screen::Playing);
+
+        (gs, ctx)
+    }
+
+    /// Presses and lets go of `code`.
+    fn tap(ctx: &Ctx, code: KeyCode) {
+        let handler = &ctx.input_handler;
+        handler.push_event(KeyEvent::new(code, KeyModifiers::NONE));
+        handler.push_event(KeyEvent::new_with_kind(code, KeyModifiers::NONE, KeyEventKind::Release));
+    }
+
+    fn screen(ctx: &Ctx) -> &HeadlessTarget {
+        ctx.cam.target::<HeadlessTarget>().unwrap()
+    }
+
+    /// Checks `text` against the golden screenshot `name` in `tests/golden`.
+    ///
+    /// Run with `UPDATE_GOLDEN=1` to write the screenshot instead, after a change that is
+    /// meant to alter what the game looks like.
+    fn assert_golden(name: &str, text: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
+            return;
+        }
+
+        let golden = fs::read_to_string(&path)
+            .unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+        assert!(
+            golden == text,
+            "the screen doesn't match {}\n--- expected\n{golden}\n--- got\n{text}",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn headless_run_matches_the_golden_screenshots() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+
+        ctx.frame(&mut gs);
+        assert_golden("first_frame.txt", &screen(&ctx).text());
+
+        for code in [KeyCode::Right, KeyCode::Right, KeyCode::Down, KeyCode::Char('.')] {
+            tap(&ctx, code);
+            ctx.frame(&mut gs);
+        }
+
+        assert_eq!(gs.ecs.fetch::<TurnCount>().0, 4);
+        assert_eq!(screen(&ctx).frames(), 5);
+        assert_golden("after_four_turns.txt", &screen(&ctx).text());
+    }
+
+    #[test]
+    fn leaving_the_title_asks_first() {
+        let mut gs = State {
+            ecs: World::new(),
+            save_path: None,
+            seed: Some(42),
+            builders: BuilderSchedule::default(),
+        };
+        register_components(&mut gs.ecs);
+        let mut ctx = Ctx::headless(100, 30);
+        ctx.push_screen(Screen::title(&gs, None));
+
+        tap(&ctx, KeyCode::Esc);
+        ctx.frame(&mut gs);
+        assert!(screen(&ctx).text().contains("Quit the game?"));
+
+        tap(&ctx, KeyCode::Char('n'));
+        ctx.frame(&mut gs);
+        let text = screen(&ctx).text();
+        assert!(!text.contains("Quit the game?"));
+        assert!(text.contains("New game"));
+    }
+
+    #[test]
+    fn headless_screen_follows_resizes() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        ctx.frame(&mut gs);
+
+        ctx.cam.target_mut::<HeadlessTarget>().unwrap().resize(120, 40);
+        ctx.cam.refresh_size();
+        ctx.frame(&mut gs);
+
+        let text = screen(&ctx).text();
+        assert_eq!(text.lines().count(), 40);
+        assert!(text.lines().all(|line| line.chars().count() == 120));
+        assert!(text.contains('@'));
+
+        ctx.cam.target_mut::<HeadlessTarget>().unwrap().resize(60, 20);
+        ctx.cam.refresh_size();
+        ctx.frame(&mut gs);
+
+        assert!(screen(&ctx).text().contains("too small"));
+        assert_eq!(screen(&ctx).cell(0, 0).map(|cell| cell.glyph), Some(' '));
+    }
+
+    #[test]
+    fn keys_pressed_while_too_small_are_dropped() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        ctx.frame(&mut gs);
+
+        ctx.cam.target_mut::<HeadlessTarget>().unwrap().resize(60, 20);
+        ctx.cam.refresh_size();
+        for code in [KeyCode::Right, KeyCode::Down, KeyCode::Char('.')] {
+            tap(&ctx, code);
+        }
+        ctx.frame(&mut gs);
+
+        ctx.cam.target_mut::<HeadlessTarget>().unwrap().resize(100, 30);
+        ctx.cam.refresh_size();
+        for _ in 0..3 {
+            ctx.frame(&mut gs);
+        }
+
+        assert_eq!(gs.ecs.fetch::<TurnCount>().0, 0);
+        assert!(ctx.input_handler.pop_key().is_none());
+    }
+
+    #[test]
+    fn overhealed_player_fills_the_hp_bar() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        {
+            let players = gs.ecs.read_storage::<Player>();
+            let mut stats = gs.ecs.write_storage::<CombatStats>();
+            for (_player, stats) in (&players, &mut stats).join() {
+                stats.hp = stats.max_hp * 3;
+            }
+        }
+
+        ctx.frame(&mut gs);
+
+        assert!(screen(&ctx).text().contains("HP: 90 / 30"));
+    }
+
+    /// Moves the player onto the down stairs of the current floor, claiming it is floor
+    /// `depth`.
+    fn stand_on_down_stairs(gs: &mut State, depth: usize) {
+        let stairs = {
+            let mut map = gs.ecs.fetch_mut::<Map>();
+            map.depth = depth;
+            map.down_stairs.unwrap()
+        };
+
+        let players = gs.ecs.read_storage::<Player>();
+        for (_player, pos) in (&players, &mut gs.ecs.write_storage::<Position>()).join() {
+            *pos = stairs;
+        }
+    }
+
+    #[test]
+    fn reaching_the_final_floor_is_not_a_win() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        ctx.frame(&mut gs);
+        gs.ecs.fetch_mut::<Map>().depth = dungeon::FINAL_DEPTH;
+
+        tap(&ctx, KeyCode::Char('.'));
+        ctx.frame(&mut gs);
+
+        assert_eq!(*gs.ecs.fetch::<RunState>(), RunState::AwaitingInput);
+        assert!(gs.has_unfinished_run());
+    }
+
+    #[test]
+    fn the_final_floors_down_stairs_win_the_run() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        ctx.frame(&mut gs);
+        stand_on_down_stairs(&mut gs, dungeon::FINAL_DEPTH);
+
+        tap(&ctx, KeyCode::Char('>'));
+        ctx.frame(&mut gs);
+
+        assert_eq!(*gs.ecs.fetch::<RunState>(), RunState::Victory);
+        assert_eq!(gs.ecs.fetch::<Map>().depth, dungeon::FINAL_DEPTH);
+        assert!(!gs.has_unfinished_run());
+        assert!(screen(&ctx).text().contains("You escaped the dungeon from depth 10!"));
+    }
+
+    #[test]
+    fn down_stairs_above_the_final_floor_lead_further_down() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        ctx.frame(&mut gs);
+        stand_on_down_stairs(&mut gs, dungeon::FINAL_DEPTH - 1);
+
+        tap(&ctx, KeyCode::Char('>'));
+        ctx.frame(&mut gs);
+
+        assert_eq!(*gs.ecs.fetch::<RunState>(), RunState::AwaitingInput);
+        assert_eq!(gs.ecs.fetch::<Map>().depth, dungeon::FINAL_DEPTH);
+    }
+
+    #[test]
+    fn a_killed_player_ends_the_run() {
+        let (mut gs, mut ctx) = headless_game(42, 100, 30);
+        ctx.frame(&mut gs);
+
+        let player = (&gs.ecs.entities(), &gs.ecs.read_storage::<Player>())
+            .join()
+            .next()
+            .map(|(entity, _player)| entity)
+            .unwrap();
+        let troll = spawner::spawn_monster(
+            &mut gs.ecs,
+            spawner::MonsterKind::Troll,
+            Position { x: 0, y: 0 },
+        );
+        gs.ecs.write_storage::<CombatStats>().get_mut(troll).unwrap().power = 1000;
+        gs.ecs
+            .write_storage::<WantsToMelee>()
+            .insert(troll, WantsToMelee { target: player })
+            .unwrap();
+
+        tap(&ctx, KeyCode::Char('.'));
+        ctx.frame(&mut gs);
+
+        assert_eq!(*gs.ecs.fetch::<RunState>(), RunState::GameOver);
+        assert!(gs.ecs.is_alive(player));
+        assert!(screen(&ctx).text().contains("You died on depth 1."));
+    }
 }
@@ -1,218 +1,607 @@
-use std::collections::HashMap;
-use bracket_lib::prelude::{field_of_view, Algorithm2D, BaseMap, Point};
-use rand::Rng;
+use bracket_lib::prelude::{Algorithm2D, BaseMap, DistanceAlg, Point, SmallVec};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use specs::Entity;
 
 use crate::{
-    components::{position::Position, renderable::Renderable},
+    components::{
+        position::Position,
+        renderable::{Layer, Renderable},
+    },
     ctx::Ctx,
-    utils::{color::Color, rectangle::Rectangle},
+    map_builders::{build_validated, connectivity, BuilderKind, BuiltMap, MapError},
+    utils::{attributes::Attributes, bitset::BitSet, color::Color, rectangle::Rectangle},
 };
 
-#[derive(Debug, Clone, Copy)]
+/// The size of the first dungeon floor. Deeper floors grow from here.
+pub const DUNGEON_SIZE: (isize, isize) = (160, 100);
+/// How much each floor grows per level of depth.
+const DUNGEON_GROWTH: (isize, isize) = (10, 6);
+/// The largest a floor is allowed to get.
+const MAX_DUNGEON_SIZE: (isize, isize) = (300, 180);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Tile {
     Wall,
     Floor,
+    DownStairs,
+    UpStairs,
 }
 
 impl Tile {
     pub fn to_renderable(&self) -> Renderable {
         match self {
+            // Warm stone, which comes out white where only the basic colours are shown
             Tile::Wall => Renderable {
                 glyph: '#',
-                fg: Color::Black,
+                fg: Color::Rgb(200, 190, 175),
                 bg: Color::Default,
+                layer: Layer::Terrain,
+                attrs: Attributes::NONE,
             },
+            // A grey from the palette, so the floor sits back behind walls and actors
             Tile::Floor => Renderable {
                 glyph: '·',
-                fg: Color::Black,
+                fg: Color::Ansi256(245),
+                bg: Color::Default,
+                layer: Layer::Terrain,
+                attrs: Attributes::NONE,
+            },
+            Tile::DownStairs => Renderable {
+                glyph: '>',
+                fg: Color::Cyan,
                 bg: Color::Default,
+                layer: Layer::Terrain,
+                attrs: Attributes::NONE,
             },
+            Tile::UpStairs => Renderable {
+                glyph: '<',
+                fg: Color::Cyan,
+                bg: Color::Default,
+                layer: Layer::Terrain,
+                attrs: Attributes::NONE,
+            },
+        }
+    }
+
+    /// How the tile looks when it has been seen before but is not in view right now.
+    pub fn to_remembered_renderable(&self) -> Renderable {
+        Renderable {
+            attrs: Attributes::DIM,
+            ..self.to_renderable()
         }
     }
 
     pub fn passable(&self) -> bool {
         match self {
             Tile::Wall => false,
-            Tile::Floor => true,
+            Tile::Floor | Tile::DownStairs | Tile::UpStairs => true,
         }
     }
 }
 
+/// A dungeon floor, stored as a dense `width` x `height` grid in row-major order.
+///
+/// The indexes rebuilt every turn are left out of save files. Loading a game or changing
+/// floors runs the `MapIndexingSystem` straight away to get them back, and until then the
+/// map reads as if nothing stood anywhere.
+#[derive(Serialize, Deserialize)]
 pub struct Map {
-    tiles: HashMap<Position, Tile>,
-    revealed_tiles: HashMap<Position, Tile>,
+    tiles: Vec<Tile>,
+    /// Tiles that have ever been seen.
+    revealed_tiles: BitSet,
+    /// Tiles that are in view this turn. Always a subset of `revealed_tiles`.
+    visible_tiles: BitSet,
+    /// Tiles that cannot be walked into this turn, because of the terrain or an entity
+    /// standing there. Rebuilt every turn by the `MapIndexingSystem`.
+    #[serde(skip)]
+    blocked: BitSet,
+    /// The entities standing on each tile. Rebuilt every turn by the `MapIndexingSystem`.
+    #[serde(skip)]
+    tile_content: Vec<Vec<Entity>>,
+    pub width: isize,
+    pub height: isize,
+    /// The seed this map was generated from, `0` for hand-built maps.
+    pub seed: u64,
+    /// How deep in the dungeon this floor is, starting at `1`.
+    pub depth: usize,
+    pub up_stairs: Option<Position>,
+    pub down_stairs: Option<Position>,
+    /// Whether diagonal steps may squeeze past the corner of a wall.
+    pub cut_corners: bool,
 }
 
 impl Map {
-    fn new(tiles: HashMap<Position, Tile>) -> Self {
+    /// Creates a `width` x `height` map where every tile is `tile`.
+    pub fn filled(width: isize, height: isize, tile: Tile, seed: u64) -> Self {
+        let len = (width.max(0) * height.max(0)) as usize;
+
         Map {
-            tiles: tiles,
-            revealed_tiles: HashMap::new()
+            tiles: vec![tile; len],
+            revealed_tiles: BitSet::new(len),
+            visible_tiles: BitSet::new(len),
+            blocked: BitSet::new(len),
+            tile_content: vec![Vec::new(); len],
+            width,
+            height,
+            seed,
+            depth: 1,
+            up_stairs: None,
+            down_stairs: None,
+            cut_corners: false,
         }
     }
 
     pub fn new_map() -> Self {
-        let mut tiles: HashMap<Position, Tile> = HashMap::new();
+        Map::filled(100, 30, Tile::Floor, 0)
+    }
+
+    /// Queues every revealed tile that falls inside the camera's view.
+    ///
+    /// Tiles in view are drawn in full colour, remembered tiles are drawn dimmed.
+    pub fn draw_map(&self, ctx: &mut Ctx) {
+        let (width, height) = ctx.get_viewport_size();
+        let origin = ctx.cam.pos;
+
+        let x_range = origin.x.max(0)..(origin.x + width as isize).min(self.width);
+        let y_range = origin.y.max(0)..(origin.y + height as isize).min(self.height);
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let idx = (y * self.width + x) as usize;
 
-        for x in 0..100 {
-            for y in 0..30 {
                 let pos = Position { x, y };
-                let tile = Tile::Floor;
 
-                tiles.insert(pos, tile);
+                if self.visible_tiles.get(idx) {
+                    ctx.set(&pos, &self.tiles[idx].to_renderable());
+                } else if self.revealed_tiles.get(idx) {
+                    ctx.set(&pos, &self.tiles[idx].to_remembered_renderable());
+                }
             }
         }
-
-        Map::new(tiles)
     }
 
-    pub fn draw_map(&self, ctx: &mut Ctx) {
-        for (pos, tile) in &self.revealed_tiles {
-            ctx.set(pos, &tile.to_renderable());
-        }
+    /// Converts `pos` to its index in the tile grid, or `None` if it is off the map.
+    pub fn idx(&self, pos: Position) -> Option<usize> {
+        self.in_bounds(pos).then(|| (pos.y * self.width + pos.x) as usize)
     }
 
     pub fn get_tile_at(&self, pos: Position) -> Option<&Tile> {
-        self.tiles.get(&pos)
+        self.idx(pos).map(|idx| &self.tiles[idx])
     }
 
-    fn apple_horizontal_line (tiles: &mut HashMap<Position, Tile>, p1: Position, x2: isize, tile :Tile) {
-        for x in p1.x.min(x2)..=p1.x.max(x2) {
-            let pos = Position {x, y: p1.y};
-            tiles.insert(pos, tile);
-
-            let pos = Position {x, y: p1.y + 1};
-            tiles.insert(pos, tile);
-
+    /// Replaces the tile at `pos`. Positions outside the map are ignored.
+    pub fn set_tile(&mut self, pos: Position, tile: Tile) {
+        if let Some(idx) = self.idx(pos) {
+            self.tiles[idx] = tile;
         }
     }
 
-    fn apple_vertical_line(tiles: &mut HashMap<Position, Tile>, p1: Position, y2: isize, tile :Tile) {
-        for y in p1.y.min(y2)..=p1.y.max(y2) {
-            let pos = Position{x: p1.x, y};
-            tiles.insert(pos, tile);
+    pub fn in_bounds(&self, pos: Position) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width && pos.y < self.height
+    }
 
-            let pos = Position{x: p1.x + 1, y};
-            tiles.insert(pos, tile);
-        }
+    pub fn is_revealed(&self, pos: Position) -> bool {
+        self.idx(pos).is_some_and(|idx| self.revealed_tiles.get(idx))
     }
 
-    pub fn new_dungeon_floor() -> (Position, Self) {
-        let mut tiles: HashMap<Position, Tile> = HashMap::new();
+    /// Returns whether `pos` is in view this turn.
+    pub fn is_visible(&self, pos: Position) -> bool {
+        self.idx(pos).is_some_and(|idx| self.visible_tiles.get(idx))
+    }
 
-        const GENERATION_OPPORTUNITIES: i32 = 60;
-        const MIN_ROOM_SIZE: isize = 6;
-        const MAX_ROOM_SIZE: isize = 30;
-        const DUNGEON_SIZE: (isize, isize) = (160, 100);
+    /// Returns whether the terrain allows a single step from `from` to the neighbouring
+    /// tile `to`, which may be diagonal.
+    ///
+    /// Unless `cut_corners` is set, a diagonal step is only allowed when both tiles it
+    /// passes between are walkable as well. Entities in the way are not checked here.
+    pub fn can_step(&self, from: Position, to: Position) -> bool {
+        let walkable = |pos: Position| self.get_tile_at(pos).is_some_and(Tile::passable);
 
-        {
-            // Fill insides
-            for x in 0..DUNGEON_SIZE.0 {
-                for y in 0..DUNGEON_SIZE.1 {
-                    let pos = Position { x, y };
-                    tiles.insert(pos, Tile::Wall);
-                }
-            }
+        if !walkable(to) {
+            return false;
         }
 
-        let mut rng = rand::thread_rng();
-        let mut rooms: Vec<Rectangle> = Vec::new();
+        let diagonal = from.x != to.x && from.y != to.y;
+        if diagonal && !self.cut_corners {
+            return walkable(Position { x: to.x, y: from.y })
+                && walkable(Position { x: from.x, y: to.y });
+        }
 
-        'room_gen: for _ in 0..GENERATION_OPPORTUNITIES {
+        true
+    }
 
-            let x = rng.gen_range(1..DUNGEON_SIZE.0) as isize;
-            let y = rng.gen_range(1..DUNGEON_SIZE.1) as isize;
-            let w = rng.gen_range(MIN_ROOM_SIZE..MAX_ROOM_SIZE); // Bias towards being longer instead of taller
-            let h = rng.gen_range(MIN_ROOM_SIZE..MAX_ROOM_SIZE);
+    pub fn apple_horizontal_line(&mut self, p1: Position, x2: isize, tile: Tile) {
+        for x in p1.x.min(x2)..=p1.x.max(x2) {
+            self.set_tile(Position { x, y: p1.y }, tile);
+            self.set_tile(Position { x, y: p1.y + 1 }, tile);
+        }
+    }
 
-            let new_room = Rectangle::new(Position { x, y }, Position { x: x + w + 5, y: y + h });
+    pub fn apple_vertical_line(&mut self, p1: Position, y2: isize, tile: Tile) {
+        for y in p1.y.min(y2)..=p1.y.max(y2) {
+            self.set_tile(Position { x: p1.x, y }, tile);
+            self.set_tile(Position { x: p1.x + 1, y }, tile);
+        }
+    }
 
-            if new_room.p2.x >= DUNGEON_SIZE.0 || new_room.p2.y >= DUNGEON_SIZE.1 {
-                continue 'room_gen;
+    /// Carves every tile inside `room` (exclusive of `p2`) into `tile`.
+    pub fn apply_room(&mut self, room: &Rectangle, tile: Tile) {
+        for x in room.p1.x..room.p2.x {
+            for y in room.p1.y..room.p2.y {
+                self.set_tile(Position { x, y }, tile);
             }
+        }
+    }
 
-            for other_room in rooms.iter() {
-                if new_room.intersect(other_room) {
-                    continue 'room_gen;
-                };
-            }
+    /// Generates dungeon floor number `depth` from `seed` using the layout algorithm `kind`.
+    ///
+    /// When `kind` is `None` the algorithm is picked from the seed as well. The same seed
+    /// and kind always produce the same tiles, spawn point and rooms for a given version
+    /// of the game, so a layout can be reproduced by sharing its seed.
+    ///
+    /// Deeper floors are larger. Every floor gets a down staircase on the tile furthest
+    /// from the spawn point, and every floor below the first gets an up staircase on the
+    /// spawn point itself. Every floor tile of the returned map can be reached from the spawn.
+    pub fn new_dungeon_floor(
+        seed: u64,
+        kind: Option<BuilderKind>,
+        depth: usize,
+    ) -> Result<BuiltMap, MapError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let kind = kind.unwrap_or_else(|| BuilderKind::random(&mut rng));
+
+        let (width, height) = Self::floor_size(depth);
+        let mut builder = kind.builder(width, height);
+        let mut built = build_validated(builder.as_mut(), &mut rng)?;
+
+        let down_stairs = connectivity::furthest_tile(&built.map, built.spawn);
+        built.map.set_tile(down_stairs, Tile::DownStairs);
+        built.map.down_stairs = Some(down_stairs);
+
+        if depth > 1 {
+            built.map.set_tile(built.spawn, Tile::UpStairs);
+            built.map.up_stairs = Some(built.spawn);
+        }
 
-            if !rooms.is_empty() {
-                let new_center = new_room.center();
-                let prev_center = rooms.last().unwrap().center();
-            
-                // Step 1: Draw vertical line from prev_center to new_center's Y
-                Self::apple_vertical_line(&mut tiles, prev_center, new_center.y, Tile::Floor);
-                
-                // Step 2: Then draw horizontal line from the end of the vertical line to new_center's X
-                let intermediate_position = Position { x: prev_center.x, y: new_center.y };
-                Self::apple_horizontal_line(&mut tiles, intermediate_position, new_center.x, Tile::Floor);
-            }
+        built.map.seed = seed;
+        built.map.depth = depth;
+
+        Ok(built)
+    }
 
+    /// Returns the width and height of floor number `depth`.
+    pub fn floor_size(depth: usize) -> (isize, isize) {
+        let growth = depth.saturating_sub(1) as isize;
 
-            rooms.push(new_room);
+        (
+            (DUNGEON_SIZE.0 + DUNGEON_GROWTH.0 * growth).min(MAX_DUNGEON_SIZE.0),
+            (DUNGEON_SIZE.1 + DUNGEON_GROWTH.1 * growth).min(MAX_DUNGEON_SIZE.1),
+        )
+    }
+
+    pub fn reveal_tile(&mut self, new_pos: Position) {
+        if let Some(idx) = self.idx(new_pos) {
+            self.revealed_tiles.insert(idx);
+            self.visible_tiles.insert(idx);
         }
+    }
 
-        for room in rooms.iter() {
-            for x in room.p1.x..room.p2.x {
+    /// Returns whether `pos` can be walked into this turn. Off-map positions are blocked.
+    pub fn is_blocked(&self, pos: Position) -> bool {
+        self.idx(pos).is_none_or(|idx| self.blocked.get(idx))
+    }
 
-                for y in room.p1.y..room.p2.y {
-                    let pos = Position { x, y };
-                    tiles.insert(pos, Tile::Floor);
-                }
+    pub fn set_blocked(&mut self, pos: Position, blocked: bool) {
+        if let Some(idx) = self.idx(pos) {
+            if blocked {
+                self.blocked.insert(idx);
+            } else {
+                self.blocked.remove(idx);
             }
         }
+    }
 
+    /// Resets the blocked tiles to just the terrain, ready for entities to be added back.
+    pub fn populate_blocked(&mut self) {
+        if self.blocked.len() == self.tiles.len() {
+            self.blocked.clear();
+        } else {
+            self.blocked = BitSet::new(self.tiles.len());
+        }
 
-        (rooms[0].center(), Map::new(tiles))
+        for (idx, tile) in self.tiles.iter().enumerate() {
+            if !tile.passable() {
+                self.blocked.insert(idx);
+            }
+        }
     }
 
-    pub fn reveal_tile(&mut self, new_pos: Position) {
-        let tile = self.tiles.get(&new_pos);
+    /// Returns the entities standing on `pos`.
+    pub fn content_at(&self, pos: Position) -> &[Entity] {
+        match self.idx(pos) {
+            Some(idx) => self.tile_content.get(idx).map_or(&[][..], Vec::as_slice),
+            None => &[],
+        }
+    }
 
-        if let Some(tile) = tile {
-            self.revealed_tiles.insert(new_pos, *tile);
+    pub fn add_content(&mut self, pos: Position, entity: Entity) {
+        if let Some(content) = self.idx(pos).and_then(|idx| self.tile_content.get_mut(idx)) {
+            content.push(entity);
         }
     }
 
-    pub fn reveal_fov(&mut self, player_pos: Position) {
-        const VISION_RADIUS: i32 = 15;
-        let player_point = Point::new(player_pos.x, player_pos.y);
-        
-        // Use the field_of_view function to get visible tiles
-        let fov_tiles: Vec<Point> = field_of_view(player_point, VISION_RADIUS, self);
-        // Reveal each tile in the FOV
-        for p in fov_tiles {
-            self.reveal_tile(Position { x: p.x as isize, y: p.y as isize});
+    pub fn clear_content_index(&mut self) {
+        self.tile_content.resize_with(self.tiles.len(), Vec::new);
+
+        for content in self.tile_content.iter_mut() {
+            content.clear();
         }
     }
+
+    /// Forgets which tiles are in view, ready for a new field of view to be revealed.
+    pub fn clear_visible(&mut self) {
+        self.visible_tiles.clear();
+    }
 }
 
 impl Algorithm2D for Map {
     fn dimensions(&self) -> Point {
-        // Return the width and height of the map. Adjust these values based on your map size.
-        Point::new(160, 100) // Example: map size 160x100
+        // Floors grow with depth, so the size has to come from the map itself
+        Point::new(self.width, self.height)
     }
     fn point2d_to_index(&self, pt: Point) -> usize {
-        // If using a fixed width grid, convert 2D coordinates to a 1D index
-        (pt.y * 160 + pt.x) as usize // Assuming a map width of 160
+        // Convert 2D coordinates to a 1D index using this map's width
+        (pt.y as isize * self.width + pt.x as isize) as usize
     }
 
     fn index_to_point2d(&self, idx: usize) -> Point {
         // Convert a 1D index back into a 2D coordinate
-        let x = (idx % 160) as isize;
-        let y = (idx / 160) as isize;
+        let x = idx as isize % self.width;
+        let y = idx as isize / self.width;
         Point::new(x, y)
     }
 }
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
+        // Walls are opaque, floors and others are transparent
+        self.tiles.get(idx) == Some(&Tile::Wall)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        let mut exits = SmallVec::new();
         let pos = self.index_to_point2d(idx);
-        match self.get_tile_at(Position { x: pos.x as isize, y: pos.y as isize}) {
-            Some(Tile::Wall) => true,  // Walls are opaque
-            _ => false,                // Floors and others are transparent
+        let pos = Position { x: pos.x as isize, y: pos.y as isize };
+
+        // Diagonal steps take a turn just like straight ones, so they cost the same
+        for step in [
+            Position { x: -1, y: 0 },
+            Position { x: 1, y: 0 },
+            Position { x: 0, y: -1 },
+            Position { x: 0, y: 1 },
+            Position { x: -1, y: -1 },
+            Position { x: 1, y: -1 },
+            Position { x: -1, y: 1 },
+            Position { x: 1, y: 1 },
+        ] {
+            let next = pos + step;
+
+            if !self.is_blocked(next) && self.can_step(pos, next) {
+                if let Some(next_idx) = self.idx(next) {
+                    exits.push((next_idx, 1.0));
+                }
+            }
+        }
+
+        exits
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        let p1 = self.index_to_point2d(idx1);
+        let p2 = self.index_to_point2d(idx2);
+        // The number of steps it takes with diagonal moves, so A* never overestimates
+        DistanceAlg::Chebyshev.distance2d(p1, p2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, hint::black_box, time::Instant};
+
+    use bracket_lib::prelude::field_of_view;
+    use rand::Rng;
+
+    use super::*;
+    use crate::utils::color::ColorSupport;
+
+    /// The parts of a generated floor that have to come out the same every time.
+    fn snapshot(built: &BuiltMap) -> (String, Position, Vec<(Position, Position)>) {
+        let tiles = serde_json::to_string(&built.map).unwrap();
+        let rooms = built.rooms.iter().map(|room| (room.p1, room.p2)).collect();
+
+        (tiles, built.spawn, rooms)
+    }
+
+    #[test]
+    fn tiles_stay_white_with_basic_colours() {
+        for tile in [Tile::Wall, Tile::Floor] {
+            let fg = tile.to_renderable().fg;
+
+            assert_eq!(fg.downsample(ColorSupport::Basic), Color::White, "{tile:?}");
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn a_deserialized_map_has_nothing_on_it() {
+        let map = Map::filled(4, 3, Tile::Floor, 0);
+        let map: Map = serde_json::from_str(&serde_json::to_string(&map).unwrap()).unwrap();
+
+        assert!(map.content_at(Position { x: 1, y: 1 }).is_empty());
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_floor() {
+        for kind in BuilderKind::ALL {
+            for depth in 1..=3 {
+                let first = Map::new_dungeon_floor(1234, Some(kind), depth).unwrap();
+                let second = Map::new_dungeon_floor(1234, Some(kind), depth).unwrap();
+
+                assert_eq!(snapshot(&first), snapshot(&second), "{kind:?} on depth {depth}");
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_generate_different_floors() {
+        for kind in BuilderKind::ALL {
+            let first = Map::new_dungeon_floor(1, Some(kind), 1).unwrap();
+            let second = Map::new_dungeon_floor(2, Some(kind), 1).unwrap();
+
+            assert_ne!(snapshot(&first), snapshot(&second), "{kind:?}");
+        }
+    }
+
+    /// The tile storage `Map` used before the dense grid, kept to compare against.
+    struct HashMapTiles {
+        tiles: HashMap<Position, Tile>,
+        revealed_tiles: HashMap<Position, Tile>,
+        width: isize,
+        height: isize,
+    }
+
+    impl Algorithm2D for HashMapTiles {
+        fn dimensions(&self) -> Point {
+            Point::new(self.width, self.height)
+        }
+    }
+
+    impl BaseMap for HashMapTiles {
+        fn is_opaque(&self, idx: usize) -> bool {
+            let pt = self.index_to_point2d(idx);
+            let pos = Position { x: pt.x as isize, y: pt.y as isize };
+
+            matches!(self.tiles.get(&pos), Some(Tile::Wall))
+        }
+    }
+
+    /// Runs `f` and returns how long it took, in milliseconds.
+    fn time_ms(f: impl FnOnce()) -> f64 {
+        let start = Instant::now();
+        f();
+        start.elapsed().as_secs_f64() * 1000.0
+    }
+
+    /// Checks that the dense grid beats the hash map it replaced on a 500x500 floor, at
+    /// filling it, field of view, revealing tiles and scanning every tile.
+    ///
+    /// Run with `cargo test --release map::tests::bench -- --ignored`.
+    #[test]
+    #[ignore = "benchmark, only meaningful in a release build"]
+    fn bench_dense_grid_against_hash_map() {
+        const SIZE: isize = 500;
+        const FOV_QUERIES: usize = 200;
+        const SCANS: usize = 20;
+        const RADIUS: i32 = 15;
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let layout: Vec<Tile> = (0..SIZE * SIZE)
+            .map(|_| if rng.gen_bool(0.3) { Tile::Wall } else { Tile::Floor })
+            .collect();
+        let centres: Vec<Position> = (0..FOV_QUERIES)
+            .map(|_| Position { x: rng.gen_range(0..SIZE), y: rng.gen_range(0..SIZE) })
+            .collect();
+        let positions = || (0..SIZE).flat_map(|y| (0..SIZE).map(move |x| Position { x, y }));
+
+        let mut hashed = HashMapTiles {
+            tiles: HashMap::new(),
+            revealed_tiles: HashMap::new(),
+            width: SIZE,
+            height: SIZE,
+        };
+        let mut grid = Map::filled(SIZE, SIZE, Tile::Wall, 0);
+
+        let fill = (
+            time_ms(|| {
+                for (pos, tile) in positions().zip(&layout) {
+                    hashed.tiles.insert(pos, *tile);
+                }
+            }),
+            time_ms(|| {
+                for (pos, tile) in positions().zip(&layout) {
+                    grid.set_tile(pos, *tile);
+                }
+            }),
+        );
+
+        let fov = (
+            time_ms(|| {
+                for centre in &centres {
+                    let point = Point::new(centre.x, centre.y);
+                    black_box(field_of_view(point, RADIUS, &hashed));
+                }
+            }),
+            time_ms(|| {
+                for centre in &centres {
+                    let point = Point::new(centre.x, centre.y);
+                    black_box(field_of_view(point, RADIUS, &grid));
+                }
+            }),
+        );
+
+        let reveal = (
+            time_ms(|| {
+                for centre in &centres {
+                    let point = Point::new(centre.x, centre.y);
+                    for pt in field_of_view(point, RADIUS, &hashed) {
+                        let pos = Position { x: pt.x as isize, y: pt.y as isize };
+                        if let Some(tile) = hashed.tiles.get(&pos) {
+                            hashed.revealed_tiles.insert(pos, *tile);
+                        }
+                    }
+                }
+            }),
+            time_ms(|| {
+                for centre in &centres {
+                    let point = Point::new(centre.x, centre.y);
+                    for pt in field_of_view(point, RADIUS, &grid) {
+                        grid.reveal_tile(Position { x: pt.x as isize, y: pt.y as isize });
+                    }
+                }
+            }),
+        );
+
+        let scans = (
+            time_ms(|| {
+                for _ in 0..SCANS {
+                    let walls = positions()
+                        .filter(|pos| hashed.tiles.get(pos) == Some(&Tile::Wall))
+                        .count();
+                    black_box(walls);
+                }
+            }),
+            time_ms(|| {
+                for _ in 0..SCANS {
+                    let walls = positions()
+                        .filter(|pos| grid.get_tile_at(*pos) == Some(&Tile::Wall))
+                        .count();
+                    black_box(walls);
+                }
+            }),
+        );
+
+        let rows = [
+            ("fill".to_string(), fill),
+            (format!("{FOV_QUERIES} FOV queries, r={RADIUS}"), fov),
+            (format!("{FOV_QUERIES} FOV reveals"), reveal),
+            (format!("{SCANS} full tile scans"), scans),
+        ];
+
+        for (label, (hashed, grid)) in rows {
+            assert!(
+                grid < hashed,
+                "{label}: {grid:.1} ms on the grid against {hashed:.1} ms on the hash map"
+            );
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    components::position::Position,
+    map::{Map, Tile},
+    utils::rectangle::Rectangle,
+};
+
+use super::{connect_points, BuiltMap, MapBuilder, MapError};
+
+/// Leaves smaller than this on either axis are not split any further.
+const MIN_LEAF_SIZE: isize = 14;
+const MIN_ROOM_SIZE: isize = 5;
+
+/// Binary space partitioning: the map is split in two over and over, a room is placed
+/// in every leaf, and sibling leaves are joined on the way back up so no room is left
+/// on its own.
+pub struct BspBuilder {
+    width: isize,
+    height: isize,
+}
+
+impl BspBuilder {
+    pub fn new(width: isize, height: isize) -> Self {
+        BspBuilder { width, height }
+    }
+
+    /// Splits `area` until it is too small, carving rooms into `map` and pushing them to
+    /// `rooms`. Returns the center of one room in the subtree, used to join it to its sibling.
+    fn split(
+        &self,
+        area: Rectangle,
+        map: &mut Map,
+        rooms: &mut Vec<Rectangle>,
+        rng: &mut StdRng,
+    ) -> Position {
+        let width = area.p2.x - area.p1.x;
+        let height = area.p2.y - area.p1.y;
+
+        let can_split_x = width >= MIN_LEAF_SIZE * 2;
+        let can_split_y = height >= MIN_LEAF_SIZE * 2;
+
+        let split_x = match (can_split_x, can_split_y) {
+            (false, false) => return self.place_room(area, map, rooms, rng),
+            (true, false) => true,
+            (false, true) => false,
+            // Prefer cutting across the longer side so leaves stay roughly square
+            (true, true) => {
+                if width > height {
+                    true
+                } else if height > width {
+                    false
+                } else {
+                    rng.gen_bool(0.5)
+                }
+            }
+        };
+
+        let (first, second) = if split_x {
+            let cut = rng.gen_range(area.p1.x + MIN_LEAF_SIZE..=area.p2.x - MIN_LEAF_SIZE);
+            (
+                Rectangle::new(
+                    area.p1,
+                    Position {
+                        x: cut,
+                        y: area.p2.y,
+                    },
+                ),
+                Rectangle::new(
+                    Position {
+                        x: cut,
+                        y: area.p1.y,
+                    },
+                    area.p2,
+                ),
+            )
+        } else {
+            let cut = rng.gen_range(area.p1.y + MIN_LEAF_SIZE..=area.p2.y - MIN_LEAF_SIZE);
+            (
+                Rectangle::new(
+                    area.p1,
+                    Position {
+                        x: area.p2.x,
+                        y: cut,
+                    },
+                ),
+                Rectangle::new(
+                    Position {
+                        x: area.p1.x,
+                        y: cut,
+                    },
+                    area.p2,
+                ),
+            )
+        };
+
+        let first_center = self.split(first, map, rooms, rng);
+        let second_center = self.split(second, map, rooms, rng);
+
+        connect_points(map, first_center, second_center);
+
+        first_center
+    }
+
+    fn place_room(
+        &self,
+        leaf: Rectangle,
+        map: &mut Map,
+        rooms: &mut Vec<Rectangle>,
+        rng: &mut StdRng,
+    ) -> Position {
+        // Keep a one tile border inside the leaf so neighbouring rooms never touch
+        let max_w = (leaf.p2.x - leaf.p1.x - 2).max(MIN_ROOM_SIZE);
+        let max_h = (leaf.p2.y - leaf.p1.y - 2).max(MIN_ROOM_SIZE);
+
+        let w = rng.gen_range(MIN_ROOM_SIZE..=max_w);
+        let h = rng.gen_range(MIN_ROOM_SIZE..=max_h);
+        let x = leaf.p1.x + 1 + rng.gen_range(0..=max_w - w);
+        let y = leaf.p1.y + 1 + rng.gen_range(0..=max_h - h);
+
+        let room = Rectangle::new(Position { x, y }, Position { x: x + w, y: y + h });
+        map.apply_room(&room, Tile::Floor);
+        rooms.push(room);
+
+        room.center()
+    }
+}
+
+impl MapBuilder for BspBuilder {
+    fn build(&mut self, rng: &mut StdRng) -> Result<BuiltMap, MapError> {
+        let mut map = Map::filled(self.width, self.height, Tile::Wall, 0);
+        let mut rooms: Vec<Rectangle> = Vec::new();
+
+        // Leave the outer edge as wall
+        let area = Rectangle::new(
+            Position { x: 1, y: 1 },
+            Position {
+                x: self.width - 2,
+                y: self.height - 2,
+            },
+        );
+        let spawn = self.split(area, &mut map, &mut rooms, rng);
+
+        Ok(BuiltMap { map, spawn, rooms })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::map_builders::assert_well_formed;
+
+    #[test]
+    fn floors_are_well_formed() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let built = BspBuilder::new(80, 50).build(&mut rng).unwrap();
+
+            assert_well_formed(&built, 80, 50);
+            // Every leaf of the split gets a room
+            assert!(built.rooms.len() > 1);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    components::position::Position,
+    map::{Map, Tile},
+};
+
+use super::{nearest_floor, BuiltMap, MapBuilder, MapError};
+
+/// Chance for each tile to start out as a wall.
+const INITIAL_WALL_CHANCE: f64 = 0.45;
+const SMOOTHING_PASSES: usize = 12;
+
+/// Organic caves: the map starts as random noise and is smoothed by repeatedly turning
+/// each tile into a wall when most of its neighbours are walls.
+pub struct CellularAutomataBuilder {
+    width: isize,
+    height: isize,
+}
+
+impl CellularAutomataBuilder {
+    pub fn new(width: isize, height: isize) -> Self {
+        CellularAutomataBuilder { width, height }
+    }
+
+    fn index(&self, x: isize, y: isize) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn is_edge(&self, x: isize, y: isize) -> bool {
+        x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1
+    }
+
+    fn wall_neighbours(&self, walls: &[bool], x: isize, y: isize) -> usize {
+        let mut count = 0;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if (dx != 0 || dy != 0) && walls[self.index(x + dx, y + dy)] {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+}
+
+impl MapBuilder for CellularAutomataBuilder {
+    fn build(&mut self, rng: &mut StdRng) -> Result<BuiltMap, MapError> {
+        // Work on a plain grid while smoothing, the map is only written once at the end
+        let mut walls: Vec<bool> = Vec::with_capacity((self.width * self.height) as usize);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                walls.push(self.is_edge(x, y) || rng.gen_bool(INITIAL_WALL_CHANCE));
+            }
+        }
+
+        for _ in 0..SMOOTHING_PASSES {
+            let mut next = vec![true; walls.len()];
+
+            for y in 1..self.height - 1 {
+                for x in 1..self.width - 1 {
+                    let neighbours = self.wall_neighbours(&walls, x, y);
+                    next[self.index(x, y)] = neighbours > 4 || neighbours == 0;
+                }
+            }
+
+            walls = next;
+        }
+
+        let mut map = Map::filled(self.width, self.height, Tile::Wall, 0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !walls[self.index(x, y)] {
+                    map.set_tile(Position { x, y }, Tile::Floor);
+                }
+            }
+        }
+
+        let center = Position {
+            x: self.width / 2,
+            y: self.height / 2,
+        };
+        let spawn = nearest_floor(&map, center).ok_or(MapError::BlockedSpawn(center))?;
+
+        Ok(BuiltMap {
+            map,
+            spawn,
+            rooms: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::map_builders::assert_well_formed;
+
+    #[test]
+    fn floors_are_well_formed() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let built = CellularAutomataBuilder::new(80, 50).build(&mut rng).unwrap();
+
+            assert_well_formed(&built, 80, 50);
+            assert!(built.rooms.is_empty());
+        }
+    }
+}
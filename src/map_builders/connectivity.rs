@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+
+use crate::{
+    components::position::Position,
+    map::{Map, Tile},
+};
+
+use super::{connect_points, BuiltMap, MapError};
+
+/// Flood-fills the passable tiles reachable from `start`.
+///
+/// ## Returns
+/// A grid of `map.width * map.height` flags, indexed by `y * width + x`, where `true`
+/// means the tile can be walked to from `start`.
+pub fn flood_fill(map: &Map, start: Position) -> Vec<bool> {
+    let mut reached = vec![false; (map.width * map.height) as usize];
+    extend_fill(map, &mut reached, start);
+
+    reached
+}
+
+/// Marks every passable tile reachable from `start` in `reached`, without walking
+/// back into tiles that are already marked.
+fn extend_fill(map: &Map, reached: &mut [bool], start: Position) {
+    if !is_passable(map, start) || reached[index(map, start)] {
+        return;
+    }
+
+    let mut open: VecDeque<Position> = VecDeque::new();
+    reached[index(map, start)] = true;
+    open.push_back(start);
+
+    while let Some(pos) = open.pop_front() {
+        for next in neighbours(pos) {
+            if is_passable(map, next) && !reached[index(map, next)] {
+                reached[index(map, next)] = true;
+                open.push_back(next);
+            }
+        }
+    }
+}
+
+/// Returns the reachable tile with the longest walk from `start`, or `start` itself when
+/// nothing else can be reached.
+pub fn furthest_tile(map: &Map, start: Position) -> Position {
+    let mut distances = vec![usize::MAX; (map.width * map.height) as usize];
+    let mut furthest = start;
+
+    if !is_passable(map, start) {
+        return furthest;
+    }
+
+    let mut open: VecDeque<Position> = VecDeque::new();
+    distances[index(map, start)] = 0;
+    open.push_back(start);
+
+    while let Some(pos) = open.pop_front() {
+        furthest = pos;
+        let distance = distances[index(map, pos)];
+
+        for next in neighbours(pos) {
+            if is_passable(map, next) && distances[index(map, next)] == usize::MAX {
+                distances[index(map, next)] = distance + 1;
+                open.push_back(next);
+            }
+        }
+    }
+
+    furthest
+}
+
+/// Returns every passable tile that cannot be reached from the spawn point.
+pub fn unreachable_tiles(built: &BuiltMap) -> Vec<Position> {
+    let reached = flood_fill(&built.map, built.spawn);
+
+    passable_tiles(&built.map)
+        .filter(|pos| !reached[index(&built.map, *pos)])
+        .collect()
+}
+
+/// Checks that the spawn point is walkable and that every floor tile can be reached from it.
+pub fn validate(built: &BuiltMap) -> Result<(), MapError> {
+    if !is_passable(&built.map, built.spawn) {
+        return Err(MapError::BlockedSpawn(built.spawn));
+    }
+
+    let unreachable = unreachable_tiles(built);
+    if !unreachable.is_empty() {
+        return Err(MapError::Unreachable {
+            tiles: unreachable.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Joins every pocket of floor that cannot be reached from the spawn point to the
+/// reachable part of the map with an extra corridor.
+///
+/// Each pocket is joined to the closest reachable tile, then flooded so the pocket, the
+/// new corridor and anything the corridor cut through count as reachable from then on.
+pub fn repair(built: &mut BuiltMap) {
+    if !is_passable(&built.map, built.spawn) {
+        return;
+    }
+
+    let mut reached = flood_fill(&built.map, built.spawn);
+    let tiles: Vec<Position> = passable_tiles(&built.map).collect();
+
+    for pocket in tiles {
+        if reached[index(&built.map, pocket)] {
+            continue;
+        }
+
+        if let Some(closest) = closest_reached(&built.map, &reached, pocket) {
+            connect_points(&mut built.map, closest, pocket);
+        }
+
+        extend_fill(&built.map, &mut reached, pocket);
+    }
+}
+
+/// Searches outwards from `start`, through walls as well, for the nearest reached tile.
+fn closest_reached(map: &Map, reached: &[bool], start: Position) -> Option<Position> {
+    let mut seen = vec![false; reached.len()];
+    let mut open: VecDeque<Position> = VecDeque::new();
+    seen[index(map, start)] = true;
+    open.push_back(start);
+
+    while let Some(pos) = open.pop_front() {
+        if reached[index(map, pos)] {
+            return Some(pos);
+        }
+
+        for next in neighbours(pos) {
+            if map.in_bounds(next) && !seen[index(map, next)] {
+                seen[index(map, next)] = true;
+                open.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+fn neighbours(pos: Position) -> [Position; 4] {
+    [
+        pos + Position { x: -1, y: 0 },
+        pos + Position { x: 1, y: 0 },
+        pos + Position { x: 0, y: -1 },
+        pos + Position { x: 0, y: 1 },
+    ]
+}
+
+fn passable_tiles(map: &Map) -> impl Iterator<Item = Position> + '_ {
+    (0..map.height)
+        .flat_map(move |y| (0..map.width).map(move |x| Position { x, y }))
+        .filter(|pos| is_passable(map, *pos))
+}
+
+fn is_passable(map: &Map, pos: Position) -> bool {
+    map.get_tile_at(pos).is_some_and(Tile::passable)
+}
+
+fn index(map: &Map, pos: Position) -> usize {
+    (pos.y * map.width + pos.x) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 20x10 map of walls with two 3x3 rooms that nothing joins up, spawning in the
+    /// left one.
+    fn two_pockets() -> BuiltMap {
+        let mut map = Map::filled(20, 10, Tile::Wall, 0);
+        for (x, y) in [(2, 2), (14, 5)] {
+            for dx in 0..3 {
+                for dy in 0..3 {
+                    map.set_tile(Position { x: x + dx, y: y + dy }, Tile::Floor);
+                }
+            }
+        }
+
+        BuiltMap {
+            map,
+            spawn: Position { x: 3, y: 3 },
+            rooms: vec![],
+        }
+    }
+
+    #[test]
+    fn validate_accepts_a_connected_floor() {
+        let mut built = two_pockets();
+        connect_points(&mut built.map, Position { x: 3, y: 3 }, Position { x: 15, y: 6 });
+
+        assert!(validate(&built).is_ok());
+    }
+
+    #[test]
+    fn validate_counts_unreachable_tiles() {
+        let built = two_pockets();
+
+        assert!(matches!(validate(&built), Err(MapError::Unreachable { tiles: 9 })));
+        assert_eq!(unreachable_tiles(&built).len(), 9);
+    }
+
+    #[test]
+    fn validate_rejects_a_spawn_in_a_wall() {
+        let mut built = two_pockets();
+        built.spawn = Position { x: 0, y: 0 };
+
+        assert!(matches!(
+            validate(&built),
+            Err(MapError::BlockedSpawn(Position { x: 0, y: 0 }))
+        ));
+    }
+
+    #[test]
+    fn repair_joins_every_pocket() {
+        let mut built = two_pockets();
+        repair(&mut built);
+
+        assert!(validate(&built).is_ok());
+        assert!(built.map.get_tile_at(Position { x: 15, y: 6 }) == Some(&Tile::Floor));
+    }
+
+    #[test]
+    fn repair_leaves_a_blocked_spawn_alone() {
+        let mut built = two_pockets();
+        built.spawn = Position { x: 0, y: 0 };
+        let before = serde_json::to_string(&built.map).unwrap();
+        repair(&mut built);
+
+        assert_eq!(serde_json::to_string(&built.map).unwrap(), before);
+        assert!(matches!(validate(&built), Err(MapError::BlockedSpawn(_))));
+    }
+
+    #[test]
+    fn furthest_tile_follows_the_walk() {
+        let mut built = two_pockets();
+        repair(&mut built);
+
+        let furthest = furthest_tile(&built.map, built.spawn);
+        assert!(furthest.x >= 14, "expected the far pocket, got {furthest:?}");
+    }
+}
@@ -0,0 +1,122 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    components::position::Position,
+    map::{Map, Tile},
+};
+
+use super::{BuiltMap, MapBuilder, MapError};
+
+/// Stop digging once this share of the map has been turned into floor.
+const FLOOR_PERCENT: isize = 40;
+/// How many steps a single digger takes before a new one starts.
+const DRUNKARD_LIFETIME: usize = 400;
+/// How many diggers are sent out before giving up, for maps too small to ever reach
+/// `FLOOR_PERCENT`.
+const MAX_DRUNKARDS: usize = 1000;
+
+/// Drunkard's walk: diggers stagger around at random from the spawn point, carving
+/// floor as they go. Every digger starts on already carved floor, so the result is
+/// always a single connected cave.
+pub struct DrunkardsWalkBuilder {
+    width: isize,
+    height: isize,
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(width: isize, height: isize) -> Self {
+        DrunkardsWalkBuilder { width, height }
+    }
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build(&mut self, rng: &mut StdRng) -> Result<BuiltMap, MapError> {
+        let mut map = Map::filled(self.width, self.height, Tile::Wall, 0);
+
+        let spawn = Position {
+            x: self.width / 2,
+            y: self.height / 2,
+        };
+        map.set_tile(spawn, Tile::Floor);
+
+        let goal = (self.width * self.height * FLOOR_PERCENT / 100) as usize;
+        let mut floor_tiles: Vec<Position> = vec![spawn];
+
+        for _ in 0..MAX_DRUNKARDS {
+            if floor_tiles.len() >= goal {
+                break;
+            }
+
+            let mut digger = floor_tiles[rng.gen_range(0..floor_tiles.len())];
+
+            for _ in 0..DRUNKARD_LIFETIME {
+                if map.get_tile_at(digger) == Some(&Tile::Wall) {
+                    map.set_tile(digger, Tile::Floor);
+                    floor_tiles.push(digger);
+                }
+
+                let step = match rng.gen_range(0..4) {
+                    0 => Position { x: -1, y: 0 },
+                    1 => Position { x: 1, y: 0 },
+                    2 => Position { x: 0, y: -1 },
+                    _ => Position { x: 0, y: 1 },
+                };
+
+                let next = digger + step;
+                // Keep the outer edge as wall
+                if next.x < 1 || next.y < 1 || next.x >= self.width - 1 || next.y >= self.height - 1
+                {
+                    continue;
+                }
+
+                digger = next;
+            }
+        }
+
+        if floor_tiles.len() < goal {
+            return Err(MapError::TooLittleFloor {
+                carved: floor_tiles.len(),
+                wanted: goal,
+            });
+        }
+
+        Ok(BuiltMap {
+            map,
+            spawn,
+            rooms: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::map_builders::assert_well_formed;
+
+    #[test]
+    fn floors_are_well_formed() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let built = DrunkardsWalkBuilder::new(80, 50).build(&mut rng).unwrap();
+
+            assert_well_formed(&built, 80, 50);
+            assert!(built.rooms.is_empty());
+            assert_eq!(built.spawn, Position { x: 40, y: 25 });
+        }
+    }
+
+    #[test]
+    fn maps_too_small_for_the_floor_give_up() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // Only the middle tile is inside the wall around the edge
+        let result = DrunkardsWalkBuilder::new(3, 3).build(&mut rng);
+
+        assert!(matches!(
+            result,
+            Err(MapError::TooLittleFloor { carved: 1, wanted: 3 })
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{components::position::Position, map::Map, utils::rectangle::Rectangle};
+
+pub mod bsp;
+pub mod cellular_automata;
+pub mod connectivity;
+pub mod drunkard;
+pub mod simple_map;
+
+/// The result of running a `MapBuilder`.
+pub struct BuiltMap {
+    pub map: Map,
+    /// Where the player starts on this floor.
+    pub spawn: Position,
+    /// The rooms carved into the map. Cave-like builders leave this empty.
+    pub rooms: Vec<Rectangle>,
+}
+
+/// A dungeon layout algorithm.
+///
+/// Builders draw every random number from the `rng` they are given, so the same seeded
+/// generator always produces the same floor.
+pub trait MapBuilder {
+    fn build(&mut self, rng: &mut StdRng) -> Result<BuiltMap, MapError>;
+}
+
+/// Why a floor could not be generated.
+#[derive(Debug)]
+pub enum MapError {
+    /// The builder could not place a single room.
+    NoRooms,
+    /// The spawn point is not on a walkable tile.
+    BlockedSpawn(Position),
+    /// Some floor tiles cannot be reached from the spawn point.
+    Unreachable { tiles: usize },
+    /// The builder gave up carving before the map had as much floor as it wanted.
+    TooLittleFloor { carved: usize, wanted: usize },
+    /// Every attempt at generating the floor failed. Holds the last error seen.
+    GenerationFailed { attempts: u32, last: Box<MapError> },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::NoRooms => write!(f, "no room could be placed"),
+            MapError::BlockedSpawn(pos) => {
+                write!(f, "spawn point ({}, {}) is not walkable", pos.x, pos.y)
+            }
+            MapError::Unreachable { tiles } => {
+                write!(
+                    f,
+                    "{tiles} floor tiles cannot be reached from the spawn point"
+                )
+            }
+            MapError::TooLittleFloor { carved, wanted } => {
+                write!(f, "only {carved} of {wanted} floor tiles could be carved")
+            }
+            MapError::GenerationFailed { attempts, last } => {
+                write!(f, "gave up after {attempts} attempts: {last}")
+            }
+        }
+    }
+}
+
+impl Error for MapError {}
+
+/// How many times a floor is regenerated before giving up.
+const MAX_GENERATION_ATTEMPTS: u32 = 10;
+
+/// Runs `builder` until it produces a floor where every tile can be reached from the spawn.
+///
+/// Disconnected pockets are first joined up with extra corridors. If the floor is still
+/// broken, it is thrown away and built again from the next numbers of `rng`, so the result
+/// stays reproducible from the seed.
+pub fn build_validated(
+    builder: &mut dyn MapBuilder,
+    rng: &mut StdRng,
+) -> Result<BuiltMap, MapError> {
+    let mut last_error = MapError::NoRooms;
+
+    for _ in 0..MAX_GENERATION_ATTEMPTS {
+        let mut built = match builder.build(rng) {
+            Ok(built) => built,
+            Err(error) => {
+                last_error = error;
+                continue;
+            }
+        };
+
+        connectivity::repair(&mut built);
+
+        match connectivity::validate(&built) {
+            Ok(()) => return Ok(built),
+            Err(error) => last_error = error,
+        }
+    }
+
+    Err(MapError::GenerationFailed {
+        attempts: MAX_GENERATION_ATTEMPTS,
+        last: Box::new(last_error),
+    })
+}
+
+/// The layout algorithms a floor can be generated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuilderKind {
+    RoomsAndCorridors,
+    Bsp,
+    CellularAutomata,
+    DrunkardsWalk,
+}
+
+impl BuilderKind {
+    pub const ALL: [BuilderKind; 4] = [
+        BuilderKind::RoomsAndCorridors,
+        BuilderKind::Bsp,
+        BuilderKind::CellularAutomata,
+        BuilderKind::DrunkardsWalk,
+    ];
+
+    /// Picks a layout algorithm using `rng`.
+    pub fn random(rng: &mut StdRng) -> Self {
+        Self::ALL[rng.gen_range(0..Self::ALL.len())]
+    }
+
+    /// Returns the name used on the command line (e.g. `--builder caves`).
+    pub fn name(self) -> &'static str {
+        match self {
+            BuilderKind::RoomsAndCorridors => "rooms",
+            BuilderKind::Bsp => "bsp",
+            BuilderKind::CellularAutomata => "caves",
+            BuilderKind::DrunkardsWalk => "drunkard",
+        }
+    }
+
+    /// Parses the name used on the command line (e.g. `--builder caves`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn builder(self, width: isize, height: isize) -> Box<dyn MapBuilder> {
+        match self {
+            BuilderKind::RoomsAndCorridors => {
+                Box::new(simple_map::SimpleMapBuilder::new(width, height))
+            }
+            BuilderKind::Bsp => Box::new(bsp::BspBuilder::new(width, height)),
+            BuilderKind::CellularAutomata => Box::new(
+                cellular_automata::CellularAutomataBuilder::new(width, height),
+            ),
+            BuilderKind::DrunkardsWalk => {
+                Box::new(drunkard::DrunkardsWalkBuilder::new(width, height))
+            }
+        }
+    }
+}
+
+/// Which layout algorithm each floor of a run is generated with.
+///
+/// Floors can be given an algorithm of their own, and the rest share the default one.
+/// Floors left with neither pick their own algorithm from their seed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuilderSchedule {
+    /// The algorithm for every floor not listed in `floors`.
+    default: Option<BuilderKind>,
+    /// Algorithms by floor number, starting at `1`.
+    floors: BTreeMap<usize, BuilderKind>,
+}
+
+impl BuilderSchedule {
+    /// Generates every floor with `kind`, or lets each pick its own when it is `None`.
+    pub fn every_floor(kind: Option<BuilderKind>) -> Self {
+        BuilderSchedule {
+            default: kind,
+            floors: BTreeMap::new(),
+        }
+    }
+
+    /// Generates floor number `depth` with `kind`, whatever the other floors use.
+    pub fn with_floor(mut self, depth: usize, kind: BuilderKind) -> Self {
+        self.floors.insert(depth, kind);
+        self
+    }
+
+    /// Returns the algorithm floor number `depth` is generated with, `None` meaning it
+    /// picks its own.
+    pub fn for_depth(&self, depth: usize) -> Option<BuilderKind> {
+        self.floors.get(&depth).copied().or(self.default)
+    }
+}
+
+/// Joins two points with an L-shaped corridor, going vertically first.
+pub fn connect_points(map: &mut Map, from: Position, to: Position) {
+    map.apple_vertical_line(from, to.y, crate::map::Tile::Floor);
+
+    let intermediate_position = Position { x: from.x, y: to.y };
+    map.apple_horizontal_line(intermediate_position, to.x, crate::map::Tile::Floor);
+}
+
+/// Returns the floor tile closest to `target`, if the map has any floor at all.
+pub fn nearest_floor(map: &Map, target: Position) -> Option<Position> {
+    let mut best: Option<(isize, Position)> = None;
+
+    for x in 0..map.width {
+        for y in 0..map.height {
+            let pos = Position { x, y };
+
+            if !map.get_tile_at(pos).is_some_and(|tile| tile.passable()) {
+                continue;
+            }
+
+            let distance = (pos.x - target.x).pow(2) + (pos.y - target.y).pow(2);
+            if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+                best = Some((distance, pos));
+            }
+        }
+    }
+
+    best.map(|(_, pos)| pos)
+}
+
+/// Checks what every builder promises about the floor it returns: the map is
+/// `width` x `height` with walls all around its edge, the spawn point is on a floor tile,
+/// and every room is floor, inside the map, clear of the other rooms, with one of them
+/// holding the spawn.
+#[cfg(test)]
+pub(crate) fn assert_well_formed(built: &BuiltMap, width: isize, height: isize) {
+    use crate::{map::Tile, utils::rectangle::Rectangle};
+
+    let map = &built.map;
+    assert_eq!((map.width, map.height), (width, height));
+
+    let edge = (0..width)
+        .flat_map(|x| [Position { x, y: 0 }, Position { x, y: height - 1 }])
+        .chain((0..height).flat_map(|y| [Position { x: 0, y }, Position { x: width - 1, y }]));
+    for pos in edge {
+        assert_eq!(map.get_tile_at(pos), Some(&Tile::Wall), "floor on the edge at {pos:?}");
+    }
+
+    assert!(
+        map.get_tile_at(built.spawn).is_some_and(|tile| tile.passable()),
+        "spawn {:?} is not on the floor",
+        built.spawn
+    );
+
+    let inside = Rectangle::new(Position { x: 1, y: 1 }, Position { x: width - 1, y: height - 1 });
+    for (i, room) in built.rooms.iter().enumerate() {
+        assert!(room.p1.x < room.p2.x && room.p1.y < room.p2.y, "empty room {room:?}");
+        assert!(
+            inside.contains(room.p1) && room.p2.x < width && room.p2.y < height,
+            "room {room:?} is outside the map"
+        );
+
+        for x in room.p1.x..room.p2.x {
+            for y in room.p1.y..room.p2.y {
+                assert_eq!(map.get_tile_at(Position { x, y }), Some(&Tile::Floor), "{room:?}");
+            }
+        }
+
+        for other in &built.rooms[i + 1..] {
+            assert!(!room.intersect(other), "rooms {room:?} and {other:?} overlap");
+        }
+    }
+
+    if !built.rooms.is_empty() {
+        assert!(
+            built.rooms.iter().any(|room| room.contains(built.spawn)),
+            "spawn {:?} is in none of the rooms",
+            built.spawn
+        );
+    }
+}
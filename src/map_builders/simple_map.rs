@@ -0,0 +1,92 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    components::position::Position,
+    map::{Map, Tile},
+    utils::rectangle::Rectangle,
+};
+
+use super::{connect_points, BuiltMap, MapBuilder, MapError};
+
+const GENERATION_OPPORTUNITIES: i32 = 60;
+const MIN_ROOM_SIZE: isize = 6;
+const MAX_ROOM_SIZE: isize = 30;
+
+/// Random non-overlapping rectangular rooms, each joined to the previous one by an
+/// L-shaped corridor.
+pub struct SimpleMapBuilder {
+    width: isize,
+    height: isize,
+}
+
+impl SimpleMapBuilder {
+    pub fn new(width: isize, height: isize) -> Self {
+        SimpleMapBuilder { width, height }
+    }
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn build(&mut self, rng: &mut StdRng) -> Result<BuiltMap, MapError> {
+        let mut map = Map::filled(self.width, self.height, Tile::Wall, 0);
+        let mut rooms: Vec<Rectangle> = Vec::new();
+
+        'room_gen: for _ in 0..GENERATION_OPPORTUNITIES {
+            let x = rng.gen_range(1..self.width);
+            let y = rng.gen_range(1..self.height);
+            let w = rng.gen_range(MIN_ROOM_SIZE..MAX_ROOM_SIZE); // Bias towards being longer instead of taller
+            let h = rng.gen_range(MIN_ROOM_SIZE..MAX_ROOM_SIZE);
+
+            let new_room = Rectangle::new(
+                Position { x, y },
+                Position {
+                    x: x + w + 5,
+                    y: y + h,
+                },
+            );
+
+            if new_room.p2.x >= self.width || new_room.p2.y >= self.height {
+                continue 'room_gen;
+            }
+
+            for other_room in rooms.iter() {
+                if new_room.intersect(other_room) {
+                    continue 'room_gen;
+                };
+            }
+
+            if let Some(prev_room) = rooms.last() {
+                connect_points(&mut map, prev_room.center(), new_room.center());
+            }
+
+            rooms.push(new_room);
+        }
+
+        for room in rooms.iter() {
+            map.apply_room(room, Tile::Floor);
+        }
+
+        let spawn = rooms.first().ok_or(MapError::NoRooms)?.center();
+
+        Ok(BuiltMap { map, spawn, rooms })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::map_builders::assert_well_formed;
+
+    #[test]
+    fn floors_are_well_formed() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let built = SimpleMapBuilder::new(80, 50).build(&mut rng).unwrap();
+
+            assert_well_formed(&built, 80, 50);
+            assert!(!built.rooms.is_empty());
+            assert_eq!(built.spawn, built.rooms[0].center());
+        }
+    }
+}
@@ -1,69 +1,770 @@
-use crossterm::{event::KeyCode, terminal};
+use bracket_lib::prelude::{DistanceAlg, Point};
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs_derive::Component;
 
-use crate::{components::position::Position, ctx::Ctx, map::Map, State};
+use crate::{
+    components::{
+        area_of_effect::AreaOfEffect,
+        combat_stats::CombatStats,
+        energy::{Energy, ACTION_COST},
+        equippable::{EquipmentSlot, Equippable},
+        equipped::Equipped,
+        in_backpack::BACKPACK_CAPACITY,
+        item::Item,
+        monster::Monster,
+        position::Position,
+        ranged::Ranged,
+        viewshed::Viewshed,
+        wants_to_drop_item::WantsToDropItem,
+        wants_to_equip_item::WantsToEquipItem,
+        wants_to_melee::WantsToMelee,
+        wants_to_pickup_item::WantsToPickupItem,
+        wants_to_unequip_item::WantsToUnequipItem,
+        wants_to_use_item::WantsToUseItem,
+    },
+    ctx::Ctx,
+    dungeon::{self, FloorChange},
+    gamelog::GameLog,
+    gui,
+    map::Map,
+    screen::Screen,
+    systems::item_use_system::ranged_targets,
+    utils::keymap::Action,
+    RunState, State, TurnCount,
+};
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Player {}
 
-pub fn get_camera_pos(gs: &State) -> Option<Position> {
+pub fn get_camera_pos(gs: &State, ctx: &Ctx) -> Option<Position> {
     let positions = gs.ecs.write_storage::<Position>();
     let players = gs.ecs.write_storage::<Player>();
 
-    let t_size = terminal::size().unwrap();
+    let t_size = ctx.get_viewport_size();
 
-    for (_player, pos) in (&players, &positions).join() {
-        return Some(Position {x: pos.x - t_size.0 as isize / 2, y: pos.y - t_size.1 as isize / 2});
-    }
-
-    None
+    (&players, &positions)
+        .join()
+        .next()
+        .map(|(_player, pos)| Position {x: pos.x - t_size.0 as isize / 2, y: pos.y - t_size.1 as isize / 2})
 }
 
-pub fn try_move_player(delta_pos: Position, ecs: &mut World) {
+/// Tries to move the player by `delta_pos`.
+///
+/// Moving into a monster attacks it instead.
+///
+/// ## Returns
+/// `true` if the player moved or attacked, `false` if the way was blocked.
+pub fn try_move_player(delta_pos: Position, ecs: &mut World) -> bool {
+    let entities = ecs.entities();
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let monsters = ecs.read_storage::<Monster>();
+    let mut wants_melee = ecs.write_storage::<WantsToMelee>();
 
     let map = ecs.fetch::<Map>();
 
-    for (_player, pos) in (&mut players, &mut positions).join() {
-        let new_pos = *pos + delta_pos;
+    let Some((entity, _player, pos, viewshed)) =
+        (&entities, &mut players, &mut positions, &mut viewsheds).join().next()
+    else {
+        return false;
+    };
+
+    let new_pos = *pos + delta_pos;
+
+    // Also keeps the player from attacking around a wall corner they couldn't step past
+    if !map.can_step(*pos, new_pos) {
+        return false;
+    }
+
+    let hostile = map
+        .content_at(new_pos)
+        .iter()
+        .find(|target| monsters.contains(**target) && combat_stats.contains(**target));
+
+    if let Some(target) = hostile {
+        wants_melee
+            .insert(entity, WantsToMelee { target: *target })
+            .expect("Add target failed");
+        return true;
+    }
+
+    if map.is_blocked(new_pos) {
+        return false;
+    }
+
+    pos.x = new_pos.x;
+    pos.y = new_pos.y;
+    viewshed.dirty = true;
+
+    true
+}
+
+/// Takes the stairs the player is standing on.
+///
+/// ## Returns
+/// `true` if the player changed floor.
+pub fn try_change_floor(direction: FloorChange, ecs: &mut World) -> bool {
+    let (changed, message) = match dungeon::change_floor(ecs, direction) {
+        Ok(true) => {
+            let depth = ecs.fetch::<Map>().depth;
+            let message = match direction {
+                FloorChange::Descend => format!("You descend to depth {depth}."),
+                FloorChange::Ascend => format!("You climb back up to depth {depth}."),
+            };
+            (true, message)
+        }
+        Ok(false) => {
+            let message = match direction {
+                FloorChange::Descend => "There is no way down here.",
+                FloorChange::Ascend => "There is no way up here.",
+            };
+            (false, message.to_string())
+        }
+        // A floor that fails to generate just keeps the player where they are
+        Err(error) => (false, format!("The stairs are blocked: {error}")),
+    };
+
+    ecs.write_resource::<GameLog>().log(message);
+
+    changed
+}
+
+/// Picks up an item from the tile the player is standing on, unless their pack is full.
+///
+/// ## Returns
+/// `true` if there was something to pick up and room for it.
+pub fn try_pick_up(ecs: &mut World) -> bool {
+    let pack_full = gui::backpack_items(ecs).len() >= BACKPACK_CAPACITY;
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let players = ecs.read_storage::<Player>();
+    let items = ecs.read_storage::<Item>();
+    let mut wants_pickup = ecs.write_storage::<WantsToPickupItem>();
 
-        if let Some(tile) = map.get_tile_at(new_pos) {
-            if !tile.passable() {
-                return;
+    let Some((player, player_pos)) = (&entities, &positions, &players)
+        .join()
+        .next()
+        .map(|(entity, pos, _player)| (entity, *pos))
+    else {
+        return false;
+    };
+
+    let item = (&entities, &items, &positions)
+        .join()
+        .find(|(_, _item, pos)| **pos == player_pos)
+        .map(|(entity, _, _)| entity);
+
+    let Some(item) = item else {
+        ecs.write_resource::<GameLog>()
+            .log("There is nothing here to pick up.");
+        return false;
+    };
+
+    // The `ItemCollectionSystem` would refuse it too, but only after the turn was spent
+    if pack_full {
+        ecs.write_resource::<GameLog>().log("Your pack is full.");
+        return false;
+    }
+
+    wants_pickup
+        .insert(
+            player,
+            WantsToPickupItem {
+                collected_by: player,
+                item,
+            },
+        )
+        .expect("Unable to pick up item");
+
+    true
+}
+
+/// Spends the energy for the action the player just took.
+pub fn spend_turn(ecs: &mut World) {
+    ecs.write_resource::<TurnCount>().0 += 1;
+
+    let players = ecs.read_storage::<Player>();
+    let mut energies = ecs.write_storage::<Energy>();
+
+    for (_player, energy) in (&players, &mut energies).join() {
+        energy.energy -= ACTION_COST;
+    }
+}
+
+/// Returns whether the player has saved up enough energy to act again.
+///
+/// A missing player counts as ready, so the scheduler never waits on them forever.
+pub fn is_ready(ecs: &World) -> bool {
+    let players = ecs.read_storage::<Player>();
+    let energies = ecs.read_storage::<Energy>();
+
+    (&players, &energies)
+        .join()
+        .next()
+        .is_none_or(|(_player, energy)| energy.can_act())
+}
+
+/// Returns whether the player has left the dungeon by the final floor's down stairs,
+/// which wins the run.
+pub fn has_won(ecs: &World) -> bool {
+    *ecs.fetch::<RunState>() == RunState::Victory
+}
+
+/// Returns whether the player has run out of health.
+pub fn is_dead(ecs: &World) -> bool {
+    let players = ecs.read_storage::<Player>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+
+    (&players, &combat_stats)
+        .join()
+        .any(|(_player, stats)| stats.hp <= 0)
+}
+
+/// Handles the player's keys for this frame.
+///
+/// At most one action is taken per call, and each key press is only acted on once.
+/// `Esc` pauses the game and `q` asks whether to quit, unless they are bound to an action.
+///
+/// ## Returns
+/// `RunState::PlayerTurn` if the player did something that takes a turn,
+/// `RunState::Victory` if they left the dungeon, otherwise `RunState::AwaitingInput`.
+pub fn player_input(gs: &mut State, ctx: &mut Ctx) -> RunState {
+    // Work through the key presses in order until one of them takes a turn
+    while let Some(key) = ctx.input_handler.pop_key() {
+        // Ctrl and Alt combinations are never movement, even on a bound key
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            continue;
+        }
+
+        let Some(action) = ctx.settings.keymap.action_for(&key.code) else {
+            match key.code {
+                KeyCode::Esc => ctx.push_screen(Screen::Paused { selected: 0 }),
+                KeyCode::Char('q') => ctx.push_screen(Screen::ConfirmQuit),
+                _ => continue,
+            }
+
+            // Whatever was pressed after it is for the new screen
+            return RunState::AwaitingInput;
+        };
+
+        let acted = match action {
+            Action::MoveN
+            | Action::MoveS
+            | Action::MoveE
+            | Action::MoveW
+            | Action::MoveNe
+            | Action::MoveNw
+            | Action::MoveSe
+            | Action::MoveSw => {
+                let delta = action.direction().expect("Movement actions have a direction");
+                try_move_player(with_held_direction(delta, ctx), &mut gs.ecs)
             }
+
+            Action::Descend if dungeon::is_on_exit(&gs.ecs) => {
+                gs.ecs
+                    .write_resource::<GameLog>()
+                    .log("You take the stairs out of the dungeon!");
+                return RunState::Victory;
+            }
+            Action::Descend => try_change_floor(FloorChange::Descend, &mut gs.ecs),
+            Action::Ascend => try_change_floor(FloorChange::Ascend, &mut gs.ecs),
+
+            Action::PickUp => try_pick_up(&mut gs.ecs),
+            Action::Inventory => return RunState::ShowInventory,
+            Action::Drop => return RunState::ShowDropItem,
+            Action::Equipment => return RunState::ShowEquipment,
+
+            // Wait a turn
+            Action::Wait => true,
+        };
+
+        if acted {
+            return RunState::PlayerTurn;
         }
+    }
+
+    RunState::AwaitingInput
+}
+
+/// Handles the player's keys while an item menu is open.
+///
+/// Items are picked by the letter they are listed under, and `Esc` closes the menu.
+/// Picking a ranged item to use asks for a target first, and picking equipment to use
+/// equips it.
+///
+/// ## Arguments
+/// - `menu`: `RunState::ShowInventory` to use the picked item, or `RunState::ShowDropItem`
+///   to drop it.
+///
+/// ## Returns
+/// `RunState::PlayerTurn` once an item was picked, `RunState::ShowTargeting` if it still
+/// needs a target, `RunState::AwaitingInput` if the menu was closed, otherwise `menu`, to
+/// keep it open.
+pub fn item_menu_input(gs: &mut State, ctx: &mut Ctx, menu: RunState) -> RunState {
+    while let Some(key) = ctx.input_handler.pop_key() {
+        let idx = match key.code {
+            KeyCode::Esc => return RunState::AwaitingInput,
+            KeyCode::Char(c @ 'a'..='z') => c as usize - 'a' as usize,
+            _ => continue,
+        };
+
+        let Some(&item) = gui::backpack_items(&gs.ecs).get(idx) else {
+            continue;
+        };
+
+        let entities = gs.ecs.entities();
+        let players = gs.ecs.read_storage::<Player>();
+        let Some((player, _player)) = (&entities, &players).join().next() else {
+            return RunState::AwaitingInput;
+        };
+
+        if menu == RunState::ShowDropItem {
+            gs.ecs
+                .write_storage::<WantsToDropItem>()
+                .insert(player, WantsToDropItem { item })
+                .expect("Unable to drop item");
+        } else if gs.ecs.read_storage::<Equippable>().contains(item) {
+            gs.ecs
+                .write_storage::<WantsToEquipItem>()
+                .insert(player, WantsToEquipItem { item })
+                .expect("Unable to equip item");
+        } else if let Some(ranged) = gs.ecs.read_storage::<Ranged>().get(item) {
+            let cursor = nearest_visible_enemy(&gs.ecs, ranged.range)
+                .or_else(|| player_position(&gs.ecs))
+                .unwrap_or(Position { x: 0, y: 0 });
 
-        pos.x = new_pos.x;
-        pos.y = new_pos.y;
+            return RunState::ShowTargeting { item, cursor };
+        } else {
+            gs.ecs
+                .write_storage::<WantsToUseItem>()
+                .insert(player, WantsToUseItem { item, target: None })
+                .expect("Unable to use item");
+        }
+
+        return RunState::PlayerTurn;
     }
+
+    menu
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Ctx) {
-    // Player movement
-    for key in ctx.input_handler.get_key_states() {
-        if key.1 == false {
+/// Handles the player's keys while the equipment screen is open.
+///
+/// Slots are picked by the letter they are listed under, which takes off the item worn
+/// there if the pack has room for it, and `Esc` closes the screen.
+///
+/// ## Returns
+/// `RunState::PlayerTurn` once an item was taken off, `RunState::AwaitingInput` if the
+/// screen was closed or the pack is full, otherwise `RunState::ShowEquipment`, to keep
+/// it open.
+pub fn equipment_input(gs: &mut State, ctx: &mut Ctx) -> RunState {
+    while let Some(key) = ctx.input_handler.pop_key() {
+        let idx = match key.code {
+            KeyCode::Esc => return RunState::AwaitingInput,
+            KeyCode::Char(c @ 'a'..='z') => c as usize - 'a' as usize,
+            _ => continue,
+        };
+
+        let Some(&slot) = EquipmentSlot::ALL.get(idx) else {
+            continue;
+        };
+        let Some(item) = equipped_item(&gs.ecs, slot) else {
             continue;
+        };
+
+        // Whatever is taken off goes in the pack, which can't hold more than the item
+        // menus can list
+        if gui::backpack_items(&gs.ecs).len() >= BACKPACK_CAPACITY {
+            gs.ecs
+                .write_resource::<GameLog>()
+                .log("Your pack is full, so there is nowhere to put it.");
+            return RunState::AwaitingInput;
         }
 
-        match key.0 {
-            KeyCode::Left => try_move_player(Position { x: -1, y: 0 }, &mut gs.ecs),
-            KeyCode::Right => try_move_player(Position { x: 1, y: 0 }, &mut gs.ecs),
-            KeyCode::Up => try_move_player(Position { x: 0, y: 1 }, &mut gs.ecs),
-            KeyCode::Down => try_move_player(Position { x: 0, y: -1 }, &mut gs.ecs),
+        let entities = gs.ecs.entities();
+        let players = gs.ecs.read_storage::<Player>();
+        let Some((player, _player)) = (&entities, &players).join().next() else {
+            return RunState::AwaitingInput;
+        };
+
+        gs.ecs
+            .write_storage::<WantsToUnequipItem>()
+            .insert(player, WantsToUnequipItem { item })
+            .expect("Unable to take off item");
 
-            KeyCode::Char('a') => try_move_player(Position { x: -1, y: 0 }, &mut gs.ecs),
-            KeyCode::Char('d') => try_move_player(Position { x: 1, y: 0 }, &mut gs.ecs),
-            KeyCode::Char('w') => try_move_player(Position { x: 0, y: 1 }, &mut gs.ecs),
-            KeyCode::Char('s') => try_move_player(Position { x: 0, y: -1 }, &mut gs.ecs),
+        return RunState::PlayerTurn;
+    }
+
+    RunState::ShowEquipment
+}
 
-            KeyCode::Char('h') => try_move_player(Position { x: -1, y: 0 }, &mut gs.ecs),
-            KeyCode::Char('l') => try_move_player(Position { x: 1, y: 0 }, &mut gs.ecs),
-            KeyCode::Char('k') => try_move_player(Position { x: 0, y: 1 }, &mut gs.ecs),
-            KeyCode::Char('j') => try_move_player(Position { x: 0, y: -1 }, &mut gs.ecs),
+/// Returns the item the player has equipped in `slot`, if any.
+pub fn equipped_item(ecs: &World, slot: EquipmentSlot) -> Option<Entity> {
+    let entities = ecs.entities();
+    let equipped = ecs.read_storage::<Equipped>();
+    let players = ecs.read_storage::<Player>();
 
+    (&entities, &equipped)
+        .join()
+        .find(|(_, worn)| worn.slot == slot && players.contains(worn.owner))
+        .map(|(item, _)| item)
+}
+
+/// Handles the player's keys while they aim the ranged `item`.
+///
+/// The movement keys move the cursor around the tiles in range, `Enter` uses the item on
+/// the tile under the cursor and `Esc` puts it away again.
+///
+/// ## Returns
+/// `RunState::PlayerTurn` once the item was used, `RunState::AwaitingInput` if aiming was
+/// cancelled, otherwise `RunState::ShowTargeting` with the cursor where it is now.
+pub fn targeting_input(gs: &mut State, ctx: &mut Ctx, item: Entity, cursor: Position) -> RunState {
+    let range = gs.ecs.read_storage::<Ranged>().get(item).map_or(0, |r| r.range);
+    let mut cursor = cursor;
+
+    while let Some(key) = ctx.input_handler.pop_key() {
+        match key.code {
+            KeyCode::Esc => return RunState::AwaitingInput,
+            KeyCode::Enter => {
+                if !targetable_tiles(&gs.ecs, range).contains(&cursor) {
+                    gs.ecs
+                        .write_resource::<GameLog>()
+                        .log("You can't aim there.");
+                    continue;
+                }
+
+                let entities = gs.ecs.entities();
+                let players = gs.ecs.read_storage::<Player>();
+                let Some((player, _player)) = (&entities, &players).join().next() else {
+                    return RunState::AwaitingInput;
+                };
+
+                // Aiming at nothing would waste the turn, so the player gets to aim again
+                let targets = {
+                    let combat_stats = gs.ecs.read_storage::<CombatStats>();
+                    ranged_targets(
+                        &gs.ecs.fetch::<Map>(),
+                        gs.ecs.read_storage::<AreaOfEffect>().get(item),
+                        cursor,
+                        player,
+                        |target| combat_stats.contains(target),
+                    )
+                };
+                if targets.is_empty() {
+                    gs.ecs.write_resource::<GameLog>().log("Nothing is there.");
+                    continue;
+                }
+
+                gs.ecs
+                    .write_storage::<WantsToUseItem>()
+                    .insert(
+                        player,
+                        WantsToUseItem {
+                            item,
+                            target: Some(cursor),
+                        },
+                    )
+                    .expect("Unable to use item");
+
+                return RunState::PlayerTurn;
+            }
             _ => {}
         }
+
+        let Some(delta) = ctx
+            .settings
+            .keymap
+            .action_for(&key.code)
+            .and_then(|action| action.direction())
+        else {
+            continue;
+        };
+
+        // The cursor stays within range, but may pass over tiles that can't be seen
+        let next = cursor + delta;
+        if player_position(&gs.ecs).is_some_and(|pos| distance(pos, next) <= range as f32) {
+            cursor = next;
+        }
+    }
+
+    RunState::ShowTargeting { item, cursor }
+}
+
+/// Returns every tile the player can see that is at most `range` tiles away, which is
+/// where ranged items can be aimed.
+pub fn targetable_tiles(ecs: &World, range: i32) -> Vec<Position> {
+    let positions = ecs.read_storage::<Position>();
+    let players = ecs.read_storage::<Player>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+
+    let Some((pos, _player, viewshed)) = (&positions, &players, &viewsheds).join().next() else {
+        return Vec::new();
+    };
+
+    viewshed
+        .visible_tiles
+        .iter()
+        .filter(|tile| distance(*pos, **tile) <= range as f32)
+        .copied()
+        .collect()
+}
+
+/// Returns where the closest monster the player can see within `range` tiles stands.
+fn nearest_visible_enemy(ecs: &World, range: i32) -> Option<Position> {
+    let player_pos = player_position(ecs)?;
+    let positions = ecs.read_storage::<Position>();
+    let monsters = ecs.read_storage::<Monster>();
+    let tiles = targetable_tiles(ecs, range);
+
+    (&positions, &monsters)
+        .join()
+        .map(|(pos, _monster)| *pos)
+        .filter(|pos| tiles.contains(pos))
+        .min_by(|a, b| distance(player_pos, *a).total_cmp(&distance(player_pos, *b)))
+}
+
+fn player_position(ecs: &World) -> Option<Position> {
+    let positions = ecs.read_storage::<Position>();
+    let players = ecs.read_storage::<Player>();
+
+    (&positions, &players).join().next().map(|(pos, _player)| *pos)
+}
+
+/// The straight-line distance between two tiles, which is how item ranges are measured.
+fn distance(a: Position, b: Position) -> f32 {
+    DistanceAlg::Pythagoras.distance2d(Point::new(a.x, a.y), Point::new(b.x, b.y))
+}
+
+/// Turns a straight step into a diagonal one while a movement key for a perpendicular
+/// direction is held down, so holding e.g. Up and Right together walks north-east.
+///
+/// Only terminals that report key releases know which keys are held, elsewhere the
+/// step is returned as is.
+fn with_held_direction(delta: Position, ctx: &Ctx) -> Position {
+    for key in ctx.input_handler.held_keys() {
+        let Some(held) = ctx
+            .settings
+            .keymap
+            .action_for(&key)
+            .and_then(|action| action.direction())
+        else {
+            continue;
+        };
+
+        let combined = delta + held;
+        let held_straight = held.x == 0 || held.y == 0;
+
+        if held_straight && combined.x.abs() == 1 && combined.y.abs() == 1 {
+            return combined;
+        }
+    }
+
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyEvent, KeyEventKind};
+
+    use super::*;
+    use crate::{
+        map_builders::BuilderSchedule,
+        spawner::{self, ItemKind},
+        systems::visibility_system::VisibilitySystem,
+        utils::settings::Settings,
+    };
+
+    /// A fresh run, with the player's entity.
+    fn started_run() -> (World, Entity) {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        crate::new_game(&mut ecs, &Settings::default(), 3, BuilderSchedule::default()).unwrap();
+
+        let player = (&ecs.entities(), &ecs.read_storage::<Player>())
+            .join()
+            .next()
+            .map(|(entity, _player)| entity)
+            .unwrap();
+
+        (ecs, player)
+    }
+
+    fn carry(ecs: &mut World, player: Entity, count: usize) {
+        for _ in 0..count {
+            spawner::give_item(ecs, player, ItemKind::HealthPotion);
+        }
+    }
+
+    fn last_message(ecs: &World) -> String {
+        ecs.fetch::<GameLog>().entries.last().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn a_full_pack_picks_nothing_up() {
+        let (mut ecs, player) = started_run();
+        carry(&mut ecs, player, BACKPACK_CAPACITY);
+        let pos = player_position(&ecs).unwrap();
+        let item = spawner::spawn_item(&mut ecs, ItemKind::HealthPotion, pos);
+
+        assert!(!try_pick_up(&mut ecs));
+        assert_eq!(last_message(&ecs), "Your pack is full.");
+        assert!(ecs.read_storage::<WantsToPickupItem>().is_empty());
+        assert!(ecs.read_storage::<Position>().contains(item));
+    }
+
+    #[test]
+    fn nothing_is_taken_off_into_a_full_pack() {
+        let (mut ecs, player) = started_run();
+        carry(&mut ecs, player, BACKPACK_CAPACITY);
+        let dagger = spawner::spawn_item(&mut ecs, ItemKind::Dagger, Position { x: 0, y: 0 });
+        ecs.write_storage::<Position>().remove(dagger);
+        ecs.write_storage::<Equipped>()
+            .insert(
+                dagger,
+                Equipped {
+                    owner: player,
+                    slot: EquipmentSlot::Melee,
+                },
+            )
+            .unwrap();
+
+        let mut gs = State {
+            ecs,
+            save_path: None,
+            seed: None,
+            builders: BuilderSchedule::default(),
+        };
+        let mut ctx = holding(&[KeyCode::Char('a')]);
+
+        assert_eq!(equipment_input(&mut gs, &mut ctx), RunState::AwaitingInput);
+        assert_eq!(equipped_item(&gs.ecs, EquipmentSlot::Melee), Some(dagger));
+        assert!(gs.ecs.read_storage::<WantsToUnequipItem>().is_empty());
+        assert_eq!(
+            last_message(&gs.ecs),
+            "Your pack is full, so there is nowhere to put it."
+        );
+    }
+
+    #[test]
+    fn the_last_free_letter_can_be_filled() {
+        let (mut ecs, player) = started_run();
+        carry(&mut ecs, player, BACKPACK_CAPACITY - 1);
+        let pos = player_position(&ecs).unwrap();
+        spawner::spawn_item(&mut ecs, ItemKind::HealthPotion, pos);
+
+        assert!(try_pick_up(&mut ecs));
+    }
+
+    /// A fresh run where the player can see, carrying a magic missile scroll.
+    fn aiming_run() -> (World, Entity) {
+        let (mut ecs, player) = started_run();
+        VisibilitySystem {}.run_now(&ecs);
+        let scroll = spawner::give_item(&mut ecs, player, ItemKind::MagicMissileScroll);
+
+        (ecs, scroll)
+    }
+
+    /// Aims `item` at `cursor` and presses enter.
+    fn aim(ecs: World, item: Entity, cursor: Position) -> (World, RunState) {
+        let mut gs = State {
+            ecs,
+            save_path: None,
+            seed: None,
+            builders: BuilderSchedule::default(),
+        };
+        let mut ctx = holding(&[KeyCode::Enter]);
+
+        let next = targeting_input(&mut gs, &mut ctx, item, cursor);
+
+        (gs.ecs, next)
+    }
+
+    #[test]
+    fn aiming_at_nothing_keeps_the_player_aiming() {
+        let (ecs, scroll) = aiming_run();
+        let range = ecs.read_storage::<Ranged>().get(scroll).unwrap().range;
+        let empty = {
+            let positions = ecs.read_storage::<Position>();
+            let combat_stats = ecs.read_storage::<CombatStats>();
+            let occupied: Vec<Position> = (&positions, &combat_stats)
+                .join()
+                .map(|(pos, _stats)| *pos)
+                .collect();
+            targetable_tiles(&ecs, range)
+                .into_iter()
+                .find(|tile| !occupied.contains(tile))
+                .unwrap()
+        };
+
+        let (ecs, next) = aim(ecs, scroll, empty);
+
+        assert_eq!(next, RunState::ShowTargeting { item: scroll, cursor: empty });
+        assert!(ecs.read_storage::<WantsToUseItem>().is_empty());
+        assert_eq!(last_message(&ecs), "Nothing is there.");
+    }
+
+    #[test]
+    fn single_target_scrolls_never_hit_their_user() {
+        let (ecs, scroll) = aiming_run();
+        let pos = player_position(&ecs).unwrap();
+
+        let (ecs, next) = aim(ecs, scroll, pos);
+
+        assert_eq!(next, RunState::ShowTargeting { item: scroll, cursor: pos });
+        assert!(ecs.read_storage::<WantsToUseItem>().is_empty());
+        assert_eq!(last_message(&ecs), "Nothing is there.");
+    }
+
+    /// A headless context where `held` are being held down, in that order.
+    fn holding(held: &[KeyCode]) -> Ctx {
+        let ctx = Ctx::headless(80, 24);
+        for code in held {
+            ctx.input_handler.push_event(KeyEvent::new(*code, KeyModifiers::NONE));
+        }
+
+        ctx
+    }
+
+    fn step(action: Action) -> Position {
+        action.direction().unwrap()
+    }
+
+    #[test]
+    fn two_held_directions_walk_diagonally() {
+        let ctx = holding(&[KeyCode::Up, KeyCode::Right]);
+
+        assert_eq!(with_held_direction(step(Action::MoveN), &ctx), step(Action::MoveNe));
+        assert_eq!(with_held_direction(step(Action::MoveE), &ctx), step(Action::MoveNe));
+    }
+
+    #[test]
+    fn any_bound_key_combines() {
+        let ctx = holding(&[KeyCode::Char('j'), KeyCode::Char('a')]);
+
+        assert_eq!(with_held_direction(step(Action::MoveS), &ctx), step(Action::MoveSw));
+    }
+
+    #[test]
+    fn released_keys_no_longer_combine() {
+        let ctx = holding(&[KeyCode::Up, KeyCode::Right]);
+        ctx.input_handler.push_event(KeyEvent::new_with_kind(
+            KeyCode::Right,
+            KeyModifiers::NONE,
+            KeyEventKind::Release,
+        ));
+
+        assert_eq!(with_held_direction(step(Action::MoveN), &ctx), step(Action::MoveN));
+    }
+
+    #[test]
+    fn opposite_and_diagonal_keys_leave_the_step_alone() {
+        let ctx = holding(&[KeyCode::Up, KeyCode::Down]);
+        assert_eq!(with_held_direction(step(Action::MoveN), &ctx), step(Action::MoveN));
+
+        let ctx = holding(&[KeyCode::Up, KeyCode::Char('n')]);
+        assert_eq!(with_held_direction(step(Action::MoveN), &ctx), step(Action::MoveN));
     }
 }
@@ -0,0 +1,297 @@
+use std::{
+    any::Any,
+    io::{stdout, Write},
+};
+
+use crossterm::{cursor, style::Print, terminal, QueueableCommand};
+
+use crate::{
+    camera::BLANK,
+    components::renderable::Renderable,
+    utils::{
+        attributes::Attributes,
+        color::{Color, ColorSupport},
+    },
+};
+
+/// The size assumed for the terminal until it can be queried.
+const FALLBACK_SIZE: (u16, u16) = (80, 24);
+
+/// A finished frame, handed to a `RenderTarget` to be shown.
+pub struct Frame<'a> {
+    /// The width and height of the frame in cells.
+    pub size: (u16, u16),
+    /// The new frame, row by row.
+    pub cells: &'a [Renderable],
+    /// The frame that was presented before this one, row by row.
+    pub previous: &'a [Renderable],
+    /// Set when whatever is on the target has to be wiped before the frame is drawn.
+    pub clear: bool,
+}
+
+/// Somewhere the camera can draw its frames to.
+pub trait RenderTarget {
+    /// Returns the width and height of the target in cells.
+    fn size(&mut self) -> (u16, u16);
+
+    /// Shows `frame`. Only the cells that differ from `frame.previous` need to be drawn,
+    /// unless `frame.clear` is set.
+    fn present(&mut self, frame: &Frame);
+
+    /// Lets callers get back to the concrete target, e.g. to inspect a headless screen.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Lets callers get back to the concrete target to change it, e.g. to resize a
+    /// headless screen.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Draws to the real terminal through crossterm.
+pub struct CrosstermTarget {
+    /// What colours the terminal can show. Richer colours are downsampled to fit.
+    color_support: ColorSupport,
+    /// The last size the terminal reported, used when it can't be asked.
+    last_size: (u16, u16),
+}
+
+impl Default for CrosstermTarget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrosstermTarget {
+    pub fn new() -> Self {
+        CrosstermTarget {
+            color_support: ColorSupport::detect(),
+            last_size: FALLBACK_SIZE,
+        }
+    }
+
+    /// Queues the escape sequences that turn `frame.previous` into `frame.cells`.
+    ///
+    /// Neighbouring changed cells on a row are sent as one run after a single cursor
+    /// move, and style escapes are only sent when the colours or attributes change.
+    fn write_changes(&self, frame: &Frame, out: &mut Vec<u8>) {
+        let width = frame.size.0 as usize;
+        // The style the terminal will draw with next, `None` until the first escape
+        let mut pen: Option<(Color, Color, Attributes)> = None;
+
+        for y in 0..frame.size.1 as usize {
+            let row = y * width;
+            let mut x = 0;
+
+            while x < width {
+                if frame.cells[row + x] == frame.previous[row + x] {
+                    x += 1;
+                    continue;
+                }
+
+                let _ = out.queue(cursor::MoveTo(x as u16, y as u16));
+
+                while x < width && frame.cells[row + x] != frame.previous[row + x] {
+                    let cell = frame.cells[row + x];
+
+                    if pen != Some((cell.fg, cell.bg, cell.attrs)) {
+                        out.extend_from_slice(cell.style_escape(self.color_support).as_bytes());
+                        pen = Some((cell.fg, cell.bg, cell.attrs));
+                    }
+
+                    let _ = out.queue(Print(cell.glyph));
+                    x += 1;
+                }
+            }
+        }
+
+        if pen.is_some() {
+            out.extend_from_slice(b"\x1b[0m");
+        }
+    }
+}
+
+impl RenderTarget for CrosstermTarget {
+    fn size(&mut self) -> (u16, u16) {
+        // Without a terminal there is nothing to ask, so keep the last known size
+        if let Ok(size) = terminal::size() {
+            self.last_size = size;
+        }
+
+        self.last_size
+    }
+
+    fn present(&mut self, frame: &Frame) {
+        let mut out: Vec<u8> = Vec::new();
+        if frame.clear {
+            let _ = out.queue(terminal::Clear(terminal::ClearType::All));
+        }
+
+        self.write_changes(frame, &mut out);
+
+        // A terminal that has gone away, e.g. with a dropped SSH session, can't be drawn
+        // to, and the game is stopped by its hangup rather than by a failed frame
+        let mut stdout = stdout();
+        let _ = stdout.write_all(&out);
+        let _ = stdout.flush();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Keeps frames in memory instead of drawing them, so the game can run without a
+/// terminal, e.g. in tests or when replaying a run.
+pub struct HeadlessTarget {
+    size: (u16, u16),
+    /// The last frame presented, row by row.
+    cells: Vec<Renderable>,
+    /// How many frames have been presented.
+    frames: usize,
+}
+
+impl HeadlessTarget {
+    /// Creates a blank `width` x `height` screen.
+    pub fn new(width: u16, height: u16) -> Self {
+        HeadlessTarget {
+            size: (width, height),
+            cells: vec![BLANK; width as usize * height as usize],
+            frames: 0,
+        }
+    }
+
+    /// Changes the size reported to the camera, as if the terminal was resized.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        *self = HeadlessTarget {
+            frames: self.frames,
+            ..HeadlessTarget::new(width, height)
+        };
+    }
+
+    /// Returns the cell at column `x` of row `y`, or `None` if it is off the screen.
+    pub fn cell(&self, x: u16, y: u16) -> Option<&Renderable> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+
+        self.cells.get(y as usize * self.size.0 as usize + x as usize)
+    }
+
+    /// Returns the glyphs of the last frame, one line per row.
+    pub fn text(&self) -> String {
+        self.cells
+            .chunks(self.size.0.max(1) as usize)
+            .map(|row| row.iter().map(|cell| cell.glyph).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+}
+
+impl RenderTarget for HeadlessTarget {
+    fn size(&mut self) -> (u16, u16) {
+        self.size
+    }
+
+    fn present(&mut self, frame: &Frame) {
+        // A frame composed for a different size would end up garbled, so drop it
+        if frame.size == self.size {
+            self.cells.copy_from_slice(frame.cells);
+            self.frames += 1;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::renderable::Layer;
+
+    const WIDTH: u16 = 10;
+    const HEIGHT: u16 = 2;
+
+    fn target() -> CrosstermTarget {
+        CrosstermTarget {
+            color_support: ColorSupport::Basic,
+            last_size: (WIDTH, HEIGHT),
+        }
+    }
+
+    fn red(glyph: char) -> Renderable {
+        Renderable {
+            glyph,
+            fg: Color::Red,
+            bg: Color::Default,
+            layer: Layer::Actors,
+            attrs: Attributes::NONE,
+        }
+    }
+
+    /// Returns what is sent to the terminal to turn `previous` into `cells`.
+    fn changes(previous: &[Renderable], cells: &[Renderable]) -> String {
+        let frame = Frame {
+            size: (WIDTH, HEIGHT),
+            cells,
+            previous,
+            clear: false,
+        };
+        let mut out = Vec::new();
+        target().write_changes(&frame, &mut out);
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn unchanged_cells_send_nothing() {
+        let mut cells = vec![BLANK; (WIDTH * HEIGHT) as usize];
+        cells[4] = red('g');
+
+        assert_eq!(changes(&cells, &cells), "");
+    }
+
+    #[test]
+    fn neighbouring_changes_are_one_run() {
+        let previous = vec![BLANK; (WIDTH * HEIGHT) as usize];
+        let mut cells = previous.clone();
+        for (x, glyph) in (3..).zip(['a', 'b', 'c']) {
+            cells[(WIDTH + x) as usize] = red(glyph);
+        }
+
+        let style = red('a').style_escape(ColorSupport::Basic);
+        assert_eq!(
+            changes(&previous, &cells),
+            format!("\x1b[2;4H{style}abc\x1b[0m")
+        );
+    }
+
+    #[test]
+    fn a_shared_style_is_only_sent_once() {
+        let previous = vec![BLANK; (WIDTH * HEIGHT) as usize];
+        let mut cells = previous.clone();
+        cells[1] = red('a');
+        cells[5] = red('b');
+        cells[(WIDTH + 2) as usize] = red('c');
+
+        let out = changes(&previous, &cells);
+        let style = red('a').style_escape(ColorSupport::Basic);
+        assert_eq!(out.matches(&style).count(), 1);
+        assert_eq!(
+            out,
+            format!("\x1b[1;2H{style}a\x1b[1;6Hb\x1b[2;3Hc\x1b[0m")
+        );
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use specs::{
+    prelude::*,
+    saveload::{DeserializeComponents, SerializeComponents, SimpleMarker, SimpleMarkerAllocator},
+};
+
+use crate::{
+    components::{
+        area_of_effect::AreaOfEffect, blocks_tile::BlocksTile, combat_stats::CombatStats,
+        confusion::Confusion, consumable::Consumable, defense_bonus::DefenseBonus, energy::Energy,
+        equippable::Equippable, equipped::Equipped, in_backpack::InBackpack,
+        inflicts_damage::InflictsDamage, item::Item, melee_power_bonus::MeleePowerBonus,
+        monster::Monster, name::Name, off_floor::OffFloor, position::Position,
+        provides_healing::ProvidesHealing, ranged::Ranged, renderable::Renderable,
+        viewshed::Viewshed,
+    },
+    dungeon::Dungeon,
+    gamelog::GameLog,
+    map::Map,
+    player::Player,
+    systems::map_indexing_system::MapIndexingSystem,
+    utils::args,
+    KillCount, RunState, TurnCount,
+};
+
+/// The version of the save file format. Bump it whenever a saved type changes shape.
+///
+/// - `1`: the first format.
+/// - `2`: adds the kill count.
+/// - `3`: replaces the dungeon's single builder with one per floor.
+pub const SAVE_VERSION: u32 = 3;
+
+/// The folder inside the user's data directory that holds the save file.
+const DATA_DIR_NAME: &str = "terminal-adventure";
+/// The name of the save file.
+const SAVE_FILE_NAME: &str = "savegame.json";
+
+/// Marks the entities that are written to the save file.
+///
+/// Every entity that should outlive a quit has to be built with
+/// `.marked::<SimpleMarker<SerializeMe>>()`.
+pub struct SerializeMe;
+
+/// Runs `$body` once for every component type that is saved, with `$ty` naming the type
+/// and `$name` holding the name it is stored under.
+///
+/// Components that only live for the length of a turn, such as `WantsToMelee`, are left out.
+macro_rules! for_each_saved_component {
+    ($ty:ident, $name:ident => $body:block) => {
+        for_each_saved_component!(@each $ty, $name, $body;
+            AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DefenseBonus, Energy,
+            Equippable, Equipped, InBackpack, InflictsDamage, Item, MeleePowerBonus, Monster,
+            Name, OffFloor, Player, Position, ProvidesHealing, Ranged, Renderable, Viewshed)
+    };
+    (@each $ty:ident, $name:ident, $body:block; $($component:ident),*) => {
+        $({
+            type $ty = $component;
+            let $name = stringify!($component);
+            $body
+        })*
+    };
+}
+
+/// The first thing read from a save file, so the format can be checked before the rest of
+/// it is parsed.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// A save file as it is written to disk.
+///
+/// There is no random number generator to save: every floor, and everything spawned on
+/// it, is derived from the dungeon seed.
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    turn: TurnCount,
+    kills: KillCount,
+    log: &'a GameLog,
+    map: &'a Map,
+    dungeon: &'a Dungeon,
+    /// Every saved component, as a list of the marked entities.
+    components: BTreeMap<&'static str, Value>,
+}
+
+/// A save file as it is read back from disk.
+#[derive(Deserialize)]
+struct LoadedSave {
+    turn: TurnCount,
+    kills: KillCount,
+    log: GameLog,
+    map: Map,
+    dungeon: Dungeon,
+    components: BTreeMap<String, Value>,
+}
+
+/// Why the game could not be saved or loaded.
+#[derive(Debug)]
+pub enum SaveError {
+    /// The save file could not be read or written.
+    Io(PathBuf, io::Error),
+    /// The save file is not valid, or the game state could not be written out.
+    Format(PathBuf, serde_json::Error),
+    /// The save file was written by an older version of the game, in a format that can't
+    /// be read any more.
+    TooOld(PathBuf, u32),
+    /// The save file was written by a newer version of the game.
+    TooNew(PathBuf, u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            SaveError::Format(path, error) => write!(f, "{}: {}", path.display(), error),
+            SaveError::TooOld(path, version) => write!(
+                f,
+                "{}: the save is from an older version of the game (format {}, this version reads {})",
+                path.display(),
+                version,
+                SAVE_VERSION
+            ),
+            SaveError::TooNew(path, version) => write!(
+                f,
+                "{}: the save is from a newer version of the game (format {}, this version reads {})",
+                path.display(),
+                version,
+                SAVE_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Returns where the game is saved.
+///
+/// The file is the one passed with `--save <path>`, or else `savegame.json` in the
+/// `terminal-adventure` folder of the user's data directory (e.g.
+/// `~/.local/share/terminal-adventure/savegame.json` on Linux). `None` if the platform
+/// has no data directory.
+pub fn save_path() -> Option<PathBuf> {
+    if let Some(path) = args::get_arg_value("--save") {
+        return Some(PathBuf::from(path));
+    }
+
+    dirs::data_dir().map(|dir| dir.join(DATA_DIR_NAME).join(SAVE_FILE_NAME))
+}
+
+/// Writes the whole game to `path`: every marked entity, the current floor, the floors
+/// left behind, the turn and kill counts and the message log.
+///
+/// The file is written next to `path` first and then moved over it, so a failed save
+/// never destroys the previous one.
+pub fn save_game(ecs: &World, path: &Path) -> Result<(), SaveError> {
+    let format_error = |e| SaveError::Format(path.into(), e);
+
+    let entities = ecs.entities();
+    let markers = ecs.read_storage::<SimpleMarker<SerializeMe>>();
+    let mut components = BTreeMap::new();
+
+    for_each_saved_component!(C, name => {
+        let list = SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
+            &(ecs.read_storage::<C>(),),
+            &entities,
+            &markers,
+            serde_json::value::Serializer,
+        )
+        .map_err(format_error)?;
+
+        components.insert(name, list);
+    });
+
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        turn: *ecs.fetch::<TurnCount>(),
+        kills: *ecs.fetch::<KillCount>(),
+        log: &ecs.fetch::<GameLog>(),
+        map: &ecs.fetch::<Map>(),
+        dungeon: &ecs.fetch::<Dungeon>(),
+        components,
+    };
+    let text = serde_json::to_string(&save).map_err(format_error)?;
+
+    let io_error = |e| SaveError::Io(path.into(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, text).map_err(io_error)?;
+    fs::rename(&partial, path).map_err(io_error)
+}
+
+/// Replaces everything in `ecs` with the game saved at `path`.
+///
+/// The game picks up at the start of the player's turn.
+///
+/// ## Returns
+/// - `Ok(())` if the save was loaded.
+/// - `Err(error)` if it could not be read, is damaged, or is in a format this version of
+///   the game doesn't read. Nothing in `ecs` changes unless the whole save loads.
+pub fn load_game(ecs: &mut World, path: &Path) -> Result<(), SaveError> {
+    let text = fs::read_to_string(path).map_err(|e| SaveError::Io(path.into(), e))?;
+    let format_error = |e| SaveError::Format(path.into(), e);
+
+    let header: SaveHeader = serde_json::from_str(&text).map_err(format_error)?;
+    // Older formats would be migrated here, once there are any worth keeping
+    if header.version < SAVE_VERSION {
+        return Err(SaveError::TooOld(path.into(), header.version));
+    }
+    if header.version > SAVE_VERSION {
+        return Err(SaveError::TooNew(path.into(), header.version));
+    }
+
+    let mut save: LoadedSave = serde_json::from_str(&text).map_err(format_error)?;
+
+    // The components are read into a world of their own, so a save that only goes wrong
+    // half way through them leaves `ecs` as it was
+    let mut loaded = World::new();
+    crate::register_components(&mut loaded);
+
+    {
+        let entities = loaded.entities();
+        let mut markers = loaded.write_storage::<SimpleMarker<SerializeMe>>();
+        let mut allocator = loaded.write_resource::<SimpleMarkerAllocator<SerializeMe>>();
+
+        for_each_saved_component!(C, name => {
+            // A component missing from the save simply isn't on any entity
+            let list = save.components.remove(name).unwrap_or(Value::Array(Vec::new()));
+
+            DeserializeComponents::<Infallible, SimpleMarker<SerializeMe>>::deserialize(
+                &mut (loaded.write_storage::<C>(),),
+                &entities,
+                &mut markers,
+                &mut *allocator,
+                list,
+            )
+            .map_err(format_error)?;
+        });
+    }
+
+    loaded.insert(save.map);
+    loaded.insert(save.dungeon);
+    loaded.insert(save.turn);
+    loaded.insert(save.kills);
+    loaded.insert(save.log);
+    loaded.insert(RunState::PreRun);
+    *ecs = loaded;
+
+    // The map's indexes aren't saved, and anything that looks at them before the first
+    // turn would find nothing there
+    MapIndexingSystem {}.run_now(ecs);
+
+    Ok(())
+}
+
+/// Deletes the save file at `path`, if there is one.
+pub fn delete_save(path: &Path) -> Result<(), SaveError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            Err(SaveError::Io(path.into(), error))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{map_builders::BuilderSchedule, utils::settings::Settings};
+
+    /// A save file path of its own for the test called `name`.
+    fn temp_save(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("roguelike-{}-{name}.json", std::process::id()))
+    }
+
+    fn fresh_world() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+
+        ecs
+    }
+
+    fn started_run(seed: u64) -> World {
+        let mut ecs = fresh_world();
+        crate::new_game(&mut ecs, &Settings::default(), seed, BuilderSchedule::default()).unwrap();
+
+        ecs
+    }
+
+    /// Everything about the run that a save has to keep, in a form that can be compared.
+    fn snapshot(ecs: &World) -> Vec<String> {
+        let entities = ecs.entities();
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let stats = ecs.read_storage::<CombatStats>();
+        let backpacks = ecs.read_storage::<InBackpack>();
+        let equipped = ecs.read_storage::<Equipped>();
+        let players = ecs.read_storage::<Player>();
+
+        let name_of = |entity: Entity| names.get(entity).map(|name| name.name.clone());
+        let mut lines: Vec<String> = (&entities, &names)
+            .join()
+            .map(|(entity, name)| {
+                format!(
+                    "{} player={} at={:?} stats={:?} carried_by={:?} worn_by={:?}",
+                    name.name,
+                    players.contains(entity),
+                    positions.get(entity),
+                    stats.get(entity).map(|s| (s.hp, s.max_hp, s.power, s.defense)),
+                    backpacks.get(entity).and_then(|b| name_of(b.owner)),
+                    equipped.get(entity).and_then(|e| name_of(e.owner)),
+                )
+            })
+            .collect();
+        lines.sort();
+
+        lines.push(serde_json::to_string(&*ecs.fetch::<Map>()).unwrap());
+        lines.push(serde_json::to_string(&*ecs.fetch::<Dungeon>()).unwrap());
+        lines.push(format!(
+            "turn={:?} kills={:?} log={:?}",
+            *ecs.fetch::<TurnCount>(),
+            *ecs.fetch::<KillCount>(),
+            ecs.fetch::<GameLog>().entries
+        ));
+
+        lines
+    }
+
+    #[test]
+    fn a_saved_run_loads_back_the_same() {
+        let mut ecs = started_run(11);
+        {
+            let players = ecs.read_storage::<Player>();
+            let mut stats = ecs.write_storage::<CombatStats>();
+            for (_player, stats) in (&players, &mut stats).join() {
+                stats.hp = 17;
+            }
+        }
+        ecs.insert(TurnCount(12));
+        ecs.insert(KillCount(3));
+        ecs.write_resource::<GameLog>().log("A goblin dies.");
+
+        let path = temp_save("round-trip");
+        save_game(&ecs, &path).unwrap();
+
+        let mut loaded = started_run(99);
+        load_game(&mut loaded, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot(&loaded), snapshot(&ecs));
+        assert_eq!(*loaded.fetch::<RunState>(), RunState::PreRun);
+    }
+
+    #[test]
+    fn a_loaded_map_is_indexed_straight_away() {
+        let ecs = started_run(11);
+        let path = temp_save("indexed");
+        save_game(&ecs, &path).unwrap();
+
+        let mut loaded = fresh_world();
+        load_game(&mut loaded, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let map = loaded.fetch::<Map>();
+        let positions = loaded.read_storage::<Position>();
+        let players = loaded.read_storage::<Player>();
+        let (player, pos) = (&loaded.entities(), &positions, &players)
+            .join()
+            .next()
+            .map(|(entity, pos, _player)| (entity, *pos))
+            .unwrap();
+        assert!(map.content_at(pos).contains(&player));
+        assert!((0..map.width).any(|x| map.is_blocked(Position { x, y: 0 })));
+    }
+
+    /// Loads a save claiming to be in format `version` over a run in progress.
+    fn load_version(name: &str, version: u32) -> (Result<(), SaveError>, bool) {
+        let path = temp_save(name);
+        fs::write(&path, format!("{{\"version\": {version}}}")).unwrap();
+
+        let mut ecs = started_run(5);
+        let before = snapshot(&ecs);
+        let result = load_game(&mut ecs, &path);
+        fs::remove_file(&path).unwrap();
+
+        (result, snapshot(&ecs) == before)
+    }
+
+    #[test]
+    fn older_formats_are_refused() {
+        let (result, untouched) = load_version("too-old", SAVE_VERSION - 1);
+
+        assert!(matches!(result, Err(SaveError::TooOld(_, version)) if version == SAVE_VERSION - 1));
+        assert!(untouched);
+    }
+
+    #[test]
+    fn saves_from_before_the_kill_count_are_refused() {
+        let ecs = started_run(3);
+        let path = temp_save("version-1");
+        save_game(&ecs, &path).unwrap();
+
+        let mut save: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        save["version"] = 1.into();
+        save.as_object_mut().unwrap().remove("kills");
+        fs::write(&path, save.to_string()).unwrap();
+
+        let result = load_game(&mut fresh_world(), &path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(SaveError::TooOld(_, 1))));
+    }
+
+    #[test]
+    fn newer_formats_are_refused() {
+        let (result, untouched) = load_version("too-new", SAVE_VERSION + 1);
+
+        assert!(matches!(result, Err(SaveError::TooNew(_, version)) if version == SAVE_VERSION + 1));
+        assert!(untouched);
+    }
+
+    #[test]
+    fn damaged_saves_are_refused() {
+        let path = temp_save("damaged");
+        fs::write(&path, format!("{{\"version\": {SAVE_VERSION}, \"turn\": ")).unwrap();
+
+        let result = load_game(&mut fresh_world(), &path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(SaveError::Format(..))));
+    }
+
+    #[test]
+    fn a_bad_component_leaves_the_run_alone() {
+        let path = temp_save("bad-component");
+        save_game(&started_run(4), &path).unwrap();
+
+        let mut save: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        save["components"]["Position"] = serde_json::json!([{ "wrong": "shape" }]);
+        fs::write(&path, save.to_string()).unwrap();
+
+        let mut ecs = started_run(5);
+        let before = snapshot(&ecs);
+        let result = load_game(&mut ecs, &path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(SaveError::Format(..))));
+        assert_eq!(snapshot(&ecs), before);
+    }
+
+    #[test]
+    fn deleting_a_missing_save_is_fine() {
+        assert!(delete_save(&temp_save("never-written")).is_ok());
+    }
+}
@@ -0,0 +1,421 @@
+use crossterm::event::KeyCode;
+
+use crate::{
+    ctx::Ctx,
+    dungeon::Dungeon,
+    gui,
+    map::Map,
+    utils::{color::Color, keymap::Action, settings::Settings},
+    GameState, KillCount, State, TurnCount,
+};
+
+/// The name shown on the title screen.
+const GAME_TITLE: &str = "Terminal Adventure";
+
+/// A screen the game can show.
+///
+/// The screens form a stack that `Ctx::main_loop` works through every frame: the top
+/// screen handles the input, and overlays are drawn over the screens beneath them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Screen {
+    /// The title menu, with the `selected` option highlighted and `message` shown below
+    /// it if something went wrong.
+    Title {
+        selected: usize,
+        message: Option<String>,
+    },
+    /// The settings that can be changed from inside the game, with `message` shown below
+    /// them if they could not be saved.
+    Settings {
+        selected: usize,
+        message: Option<String>,
+    },
+    /// The game itself.
+    Playing,
+    /// The pause menu, over the game.
+    Paused { selected: usize },
+    /// Asks whether the player really wants to quit, over the game.
+    ConfirmQuit,
+    /// The run is over, because the player died or because they `won`. Shows how it went.
+    RunOver { won: bool },
+}
+
+/// What the title menu offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TitleOption {
+    NewGame,
+    Continue,
+    Settings,
+    Quit,
+}
+
+impl TitleOption {
+    fn label(&self) -> &'static str {
+        match self {
+            TitleOption::NewGame => "New game",
+            TitleOption::Continue => "Continue",
+            TitleOption::Settings => "Settings",
+            TitleOption::Quit => "Quit",
+        }
+    }
+}
+
+/// What the pause menu offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseOption {
+    Resume,
+    QuitToTitle,
+    Quit,
+}
+
+impl PauseOption {
+    const ALL: [PauseOption; 3] = [
+        PauseOption::Resume,
+        PauseOption::QuitToTitle,
+        PauseOption::Quit,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            PauseOption::Resume => "Resume",
+            PauseOption::QuitToTitle => "Save and quit to title",
+            PauseOption::Quit => "Quit game",
+        }
+    }
+}
+
+/// A key press that means something in a menu.
+enum MenuKey {
+    /// Highlight the option at this index.
+    Select(usize),
+    /// Pick the option at this index.
+    Choose(usize),
+    /// Leave the menu.
+    Back,
+    /// Quit the game, with `q`.
+    Quit,
+}
+
+impl Screen {
+    /// The title menu, with the option most likely wanted already highlighted: `Continue`
+    /// if there is a game to continue, otherwise `New game`.
+    pub fn title(gs: &State, message: Option<String>) -> Self {
+        let selected = title_options(gs)
+            .iter()
+            .position(|option| *option == TitleOption::Continue)
+            .unwrap_or(0);
+
+        Screen::Title { selected, message }
+    }
+
+    /// Whether the screens beneath this one stay visible around it.
+    pub fn is_overlay(&self) -> bool {
+        matches!(
+            self,
+            Screen::Paused { .. } | Screen::ConfirmQuit | Screen::RunOver { .. }
+        )
+    }
+
+    /// Handles the input for this screen, which has to be the top one. Moving to another
+    /// screen goes through the screen stack in `ctx`.
+    pub fn update(&self, gs: &mut State, ctx: &mut Ctx) {
+        match self {
+            Screen::Title { selected, message } => title_input(gs, ctx, *selected, message),
+            Screen::Settings { selected, .. } => settings_input(ctx, *selected),
+            Screen::Playing => gs.tick(ctx),
+            Screen::Paused { selected } => pause_input(gs, ctx, *selected),
+            Screen::ConfirmQuit => confirm_quit_input(ctx),
+            Screen::RunOver { .. } => run_over_input(gs, ctx),
+        }
+    }
+
+    /// Draws this screen over whatever was drawn before it this frame.
+    pub fn draw(&self, gs: &State, ctx: &mut Ctx) {
+        let screen_size = ctx.get_terminal_size();
+
+        match self {
+            Screen::Title { selected, message } => {
+                draw_title(gs, ctx, *selected, message.as_deref())
+            }
+            Screen::Settings { selected, message } => {
+                let lines = settings_lines(&ctx.settings);
+                let bottom = gui::draw_menu(
+                    ctx,
+                    screen_size,
+                    "Settings",
+                    "Enter to change, Esc to go back",
+                    &lines,
+                    Some(*selected),
+                );
+                if let Some(message) = message {
+                    draw_message(ctx, screen_size.0, bottom, message);
+                }
+            }
+            Screen::Playing => gs.draw(ctx),
+            Screen::Paused { selected } => {
+                let lines: Vec<String> = PauseOption::ALL
+                    .iter()
+                    .map(|option| option.label().to_string())
+                    .collect();
+                gui::draw_menu(
+                    ctx,
+                    gui::Layout::new(screen_size).viewport,
+                    "Paused",
+                    "Esc to resume",
+                    &lines,
+                    Some(*selected),
+                );
+            }
+            Screen::ConfirmQuit => {
+                let question = if gs.has_unfinished_run() {
+                    "Save and quit the game?"
+                } else {
+                    "Quit the game?"
+                };
+                let lines = [
+                    question.to_string(),
+                    String::new(),
+                    "(y) Yes  (n) No".to_string(),
+                ];
+                gui::draw_menu(
+                    ctx,
+                    gui::Layout::new(screen_size).viewport,
+                    "Quit",
+                    "Esc to cancel",
+                    &lines,
+                    None,
+                );
+            }
+            Screen::RunOver { won } => draw_run_over(gs, ctx, *won),
+        }
+    }
+}
+
+/// Returns what the title menu offers right now. `Continue` is only there if there is a
+/// run to go back to, either still loaded or in the save file.
+fn title_options(gs: &State) -> Vec<TitleOption> {
+    let can_continue =
+        gs.has_unfinished_run() || gs.save_path.as_deref().is_some_and(|path| path.exists());
+
+    [
+        TitleOption::NewGame,
+        TitleOption::Continue,
+        TitleOption::Settings,
+        TitleOption::Quit,
+    ]
+    .into_iter()
+    .filter(|option| *option != TitleOption::Continue || can_continue)
+    .collect()
+}
+
+/// Takes key presses until one of them means something in a menu of `len` options.
+///
+/// The up and down movement keys move the highlight, wrapping around at the ends,
+/// `Enter` picks the highlighted option, `Esc` leaves the menu and `q` quits.
+fn menu_key(ctx: &mut Ctx, selected: usize, len: usize) -> Option<MenuKey> {
+    while let Some(key) = ctx.input_handler.pop_key() {
+        match key.code {
+            KeyCode::Enter => return Some(MenuKey::Choose(selected)),
+            KeyCode::Esc => return Some(MenuKey::Back),
+            KeyCode::Char('q') => return Some(MenuKey::Quit),
+            _ => {}
+        }
+
+        match ctx.settings.keymap.action_for(&key.code) {
+            Some(Action::MoveN) => return Some(MenuKey::Select((selected + len - 1) % len)),
+            Some(Action::MoveS) => return Some(MenuKey::Select((selected + 1) % len)),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn title_input(gs: &mut State, ctx: &mut Ctx, selected: usize, message: &Option<String>) {
+    let options = title_options(gs);
+
+    let chosen = match menu_key(ctx, selected, options.len()) {
+        Some(MenuKey::Select(selected)) => {
+            ctx.replace_screen(Screen::Title {
+                selected,
+                message: message.clone(),
+            });
+            return;
+        }
+        Some(MenuKey::Choose(idx)) => options[idx],
+        Some(MenuKey::Back | MenuKey::Quit) => TitleOption::Quit,
+        None => return,
+    };
+
+    match chosen {
+        TitleOption::NewGame => match gs.new_game(&ctx.settings) {
+            Ok(()) => ctx.push_screen(Screen::Playing),
+            Err(error) => ctx.replace_screen(Screen::title(
+                gs,
+                Some(format!("Could not generate the dungeon: {error}")),
+            )),
+        },
+        TitleOption::Continue => match gs.continue_game() {
+            Ok(()) => ctx.push_screen(Screen::Playing),
+            Err(error) => ctx.replace_screen(Screen::title(
+                gs,
+                Some(format!("Could not load the saved game: {error}")),
+            )),
+        },
+        TitleOption::Settings => ctx.push_screen(Screen::Settings {
+            selected: 0,
+            message: None,
+        }),
+        TitleOption::Quit => ctx.push_screen(Screen::ConfirmQuit),
+    }
+}
+
+/// Returns one line per setting, in the order the settings screen lists them.
+fn settings_lines(settings: &Settings) -> Vec<String> {
+    let on_off = |on: bool| if on { "On" } else { "Off" };
+
+    vec![format!(
+        "Cut corners on diagonal moves: {}",
+        on_off(settings.cut_corners)
+    )]
+}
+
+/// Handles the settings screen. Every change is saved to the settings file straight
+/// away, and corner cutting only applies to runs started after the change, since each
+/// run keeps its own rules.
+fn settings_input(ctx: &mut Ctx, selected: usize) {
+    let len = settings_lines(&ctx.settings).len();
+
+    match menu_key(ctx, selected, len) {
+        Some(MenuKey::Select(selected)) => ctx.replace_screen(Screen::Settings {
+            selected,
+            message: None,
+        }),
+        Some(MenuKey::Choose(0)) => {
+            ctx.settings.cut_corners = !ctx.settings.cut_corners;
+
+            // The change still holds for this session if it can't be saved
+            let message = ctx
+                .settings
+                .save()
+                .err()
+                .map(|error| format!("Could not save the settings: {error}"));
+            ctx.replace_screen(Screen::Settings { selected, message });
+        }
+        Some(MenuKey::Choose(_)) | None => {}
+        Some(MenuKey::Back | MenuKey::Quit) => ctx.pop_screen(),
+    }
+}
+
+fn pause_input(gs: &mut State, ctx: &mut Ctx, selected: usize) {
+    let chosen = match menu_key(ctx, selected, PauseOption::ALL.len()) {
+        Some(MenuKey::Select(selected)) => {
+            ctx.replace_screen(Screen::Paused { selected });
+            return;
+        }
+        Some(MenuKey::Choose(idx)) => PauseOption::ALL[idx],
+        Some(MenuKey::Back) => PauseOption::Resume,
+        Some(MenuKey::Quit) => PauseOption::Quit,
+        None => return,
+    };
+
+    match chosen {
+        PauseOption::Resume => ctx.pop_screen(),
+        PauseOption::QuitToTitle => {
+            let message = gs
+                .save()
+                .err()
+                .map(|error| format!("Could not save the game: {error}"));
+            ctx.reset_screens(Screen::title(gs, message));
+        }
+        PauseOption::Quit => ctx.replace_screen(Screen::ConfirmQuit),
+    }
+}
+
+fn confirm_quit_input(ctx: &mut Ctx) {
+    while let Some(key) = ctx.input_handler.pop_key() {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => return ctx.quit(),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => return ctx.pop_screen(),
+            _ => {}
+        }
+    }
+}
+
+fn run_over_input(gs: &State, ctx: &mut Ctx) {
+    while let Some(key) = ctx.input_handler.pop_key() {
+        match key.code {
+            KeyCode::Enter => return ctx.reset_screens(Screen::title(gs, None)),
+            KeyCode::Char('q') | KeyCode::Esc => return ctx.quit(),
+            _ => {}
+        }
+    }
+}
+
+fn draw_title(gs: &State, ctx: &mut Ctx, selected: usize, message: Option<&str>) {
+    let (width, height) = ctx.get_terminal_size();
+    let lines: Vec<String> = title_options(gs)
+        .iter()
+        .map(|option| option.label().to_string())
+        .collect();
+
+    let bottom = gui::draw_menu(
+        ctx,
+        (width, height),
+        GAME_TITLE,
+        "Enter to choose",
+        &lines,
+        Some(selected),
+    );
+
+    if let Some(message) = message {
+        draw_message(ctx, width, bottom, message);
+    }
+}
+
+/// Draws `message` in red under a menu whose bottom is on row `bottom`, centred on a
+/// screen `width` cells wide.
+fn draw_message(ctx: &mut Ctx, width: u16, bottom: isize, message: &str) {
+    for (i, line) in gui::wrap(message, width.saturating_sub(4) as usize)
+        .iter()
+        .enumerate()
+    {
+        let x = (width as isize - line.chars().count() as isize) / 2;
+        ctx.print(x, bottom + 2 + i as isize, line, Color::Red);
+    }
+}
+
+/// Draws how the run went over the middle of the world view.
+fn draw_run_over(gs: &State, ctx: &mut Ctx, won: bool) {
+    let depth = gs.ecs.fetch::<Map>().depth;
+    let dungeon = gs.ecs.fetch::<Dungeon>();
+    let deepest = dungeon.deepest_floor().max(depth);
+    let seed = dungeon.seed;
+    let turns = gs.ecs.fetch::<TurnCount>().0;
+    let kills = gs.ecs.fetch::<KillCount>().0;
+
+    let (title, outcome) = if won {
+        ("Victory", format!("You escaped the dungeon from depth {depth}!"))
+    } else {
+        ("Game over", format!("You died on depth {depth}."))
+    };
+
+    let lines = [
+        outcome,
+        String::new(),
+        format!("Deepest floor: {deepest}"),
+        format!("Turns taken: {turns}"),
+        format!("Monsters slain: {kills}"),
+        format!("Seed: {seed}"),
+    ];
+
+    let layout = gui::Layout::new(ctx.get_terminal_size());
+    gui::draw_menu(
+        ctx,
+        layout.viewport,
+        title,
+        "Enter for the title, q to quit",
+        &lines,
+        None,
+    );
+}
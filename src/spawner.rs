@@ -0,0 +1,487 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use specs::{
+    prelude::*,
+    saveload::{MarkedBuilder, SimpleMarker},
+};
+
+use crate::{
+    components::{
+        area_of_effect::AreaOfEffect,
+        blocks_tile::BlocksTile,
+        combat_stats::CombatStats,
+        confusion::Confusion,
+        consumable::Consumable,
+        defense_bonus::DefenseBonus,
+        energy::Energy,
+        equippable::{EquipmentSlot, Equippable},
+        in_backpack::InBackpack,
+        inflicts_damage::InflictsDamage,
+        item::Item,
+        melee_power_bonus::MeleePowerBonus,
+        monster::Monster,
+        name::Name,
+        position::Position,
+        provides_healing::ProvidesHealing,
+        ranged::Ranged,
+        renderable::{Layer, Renderable},
+        viewshed::Viewshed,
+        wants_to_equip_item::WantsToEquipItem,
+    },
+    map::{Map, Tile},
+    saveload::SerializeMe,
+    utils::{attributes::Attributes, color::Color, rectangle::Rectangle},
+};
+
+/// Mixed into the floor seed so spawning draws different numbers than map generation.
+const SPAWN_SALT: u64 = 0x5EED_0F5B_A7ED;
+/// Mixed into the floor seed so items don't move the monsters around, and the other way round.
+const ITEM_SALT: u64 = 0x17E4_5EED_0F17;
+/// How many tiles of open floor a cave floor gets per monster.
+const CAVE_TILES_PER_MONSTER: usize = 150;
+/// How many tiles of open floor a cave floor gets per item.
+const CAVE_TILES_PER_ITEM: usize = 400;
+/// How many items a single room can hold.
+const MAX_ITEMS_PER_ROOM: usize = 1;
+/// Monsters on cave floors keep at least this far away from where the player arrives.
+const SAFE_RADIUS: isize = 8;
+
+/// Every kind of item that can be spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    HealthPotion,
+    MagicMissileScroll,
+    FireballScroll,
+    ConfusionScroll,
+    Dagger,
+    Longsword,
+    Shield,
+    TowerShield,
+    LeatherArmour,
+    RingOfStrength,
+}
+
+/// Every kind of monster that can be spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonsterKind {
+    Goblin,
+    Orc,
+    Troll,
+}
+
+/// A weighted list of the monsters or items that can show up on a floor.
+pub struct SpawnTable<K> {
+    entries: Vec<(K, u32)>,
+    total_weight: u32,
+}
+
+impl SpawnTable<MonsterKind> {
+    /// Builds the monster table for floor number `depth`.
+    ///
+    /// Goblins are everywhere but get rarer as the dungeon goes on, orcs show up from the
+    /// second floor and trolls from the fourth.
+    pub fn monsters_for_depth(depth: usize) -> Self {
+        let depth = depth as u32;
+        let mut table = SpawnTable::new();
+
+        table.add(MonsterKind::Goblin, 10u32.saturating_sub(depth).max(3));
+        if depth >= 2 {
+            table.add(MonsterKind::Orc, 2 + depth);
+        }
+        if depth >= 4 {
+            table.add(MonsterKind::Troll, depth - 3);
+        }
+
+        table
+    }
+}
+
+impl SpawnTable<ItemKind> {
+    /// Builds the item table for floor number `depth`.
+    ///
+    /// Healing potions are the most common find. Fireball scrolls and rings show up from
+    /// the second floor, and the heavier weapons and shields from the third, getting more
+    /// common further down.
+    pub fn items_for_depth(depth: usize) -> Self {
+        let depth = depth as u32;
+        let mut table = SpawnTable::new();
+
+        table.add(ItemKind::HealthPotion, 7);
+        table.add(ItemKind::MagicMissileScroll, 4);
+        table.add(ItemKind::ConfusionScroll, 2);
+        table.add(ItemKind::Dagger, 3);
+        table.add(ItemKind::Shield, 3);
+        table.add(ItemKind::LeatherArmour, 2);
+        if depth >= 2 {
+            table.add(ItemKind::FireballScroll, depth);
+            table.add(ItemKind::RingOfStrength, 1);
+        }
+        if depth >= 3 {
+            table.add(ItemKind::Longsword, depth - 2);
+            table.add(ItemKind::TowerShield, depth - 2);
+        }
+
+        table
+    }
+}
+
+impl<K: Copy> SpawnTable<K> {
+    fn new() -> Self {
+        SpawnTable {
+            entries: Vec::new(),
+            total_weight: 0,
+        }
+    }
+
+    fn add(&mut self, kind: K, weight: u32) {
+        self.entries.push((kind, weight));
+        self.total_weight += weight;
+    }
+
+    /// Picks an entry, or `None` if the table is empty.
+    pub fn roll(&self, rng: &mut StdRng) -> Option<K> {
+        if self.total_weight == 0 {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0..self.total_weight);
+        for (kind, weight) in &self.entries {
+            if roll < *weight {
+                return Some(*kind);
+            }
+            roll -= weight;
+        }
+
+        None
+    }
+}
+
+/// How many monsters a single room can hold on floor number `depth`.
+fn max_monsters_per_room(depth: usize) -> usize {
+    (2 + depth / 2).min(6)
+}
+
+/// Fills the current `Map` resource with monsters and items.
+///
+/// Rooms other than the one holding `spawn` get a few monsters and items each. Floors
+/// without rooms, such as caves, get them scattered over their open floor instead. The
+/// same floor always gets the same monsters and items, as the numbers come from the
+/// floor seed.
+pub fn spawn_floor(ecs: &mut World, rooms: &[Rectangle], spawn: Position) {
+    let (mut rng, depth, positions) = {
+        let map = ecs.fetch::<Map>();
+        let mut rng = StdRng::seed_from_u64(map.seed ^ SPAWN_SALT);
+        let max_per_room = max_monsters_per_room(map.depth);
+
+        let positions = if rooms.is_empty() {
+            scatter_positions(&map, spawn, CAVE_TILES_PER_MONSTER, &mut rng)
+        } else {
+            room_positions(&map, rooms, spawn, max_per_room, &mut rng)
+        };
+
+        (rng, map.depth, positions)
+    };
+
+    let table = SpawnTable::monsters_for_depth(depth);
+
+    for pos in positions {
+        if let Some(kind) = table.roll(&mut rng) {
+            spawn_monster(ecs, kind, pos);
+        }
+    }
+
+    spawn_items(ecs, rooms, spawn);
+}
+
+/// Scatters items over the current `Map` resource, the same way `spawn_floor` places
+/// monsters.
+fn spawn_items(ecs: &mut World, rooms: &[Rectangle], spawn: Position) {
+    let (mut rng, depth, positions) = {
+        let map = ecs.fetch::<Map>();
+        let mut rng = StdRng::seed_from_u64(map.seed ^ ITEM_SALT);
+
+        let positions = if rooms.is_empty() {
+            scatter_positions(&map, spawn, CAVE_TILES_PER_ITEM, &mut rng)
+        } else {
+            room_positions(&map, rooms, spawn, MAX_ITEMS_PER_ROOM, &mut rng)
+        };
+
+        (rng, map.depth, positions)
+    };
+
+    let table = SpawnTable::items_for_depth(depth);
+
+    for pos in positions {
+        if let Some(kind) = table.roll(&mut rng) {
+            spawn_item(ecs, kind, pos);
+        }
+    }
+}
+
+/// Picks up to `max_per_room` free tiles in every room but the one holding `spawn`.
+fn room_positions(
+    map: &Map,
+    rooms: &[Rectangle],
+    spawn: Position,
+    max_per_room: usize,
+    rng: &mut StdRng,
+) -> Vec<Position> {
+    let mut positions: Vec<Position> = Vec::new();
+
+    for room in rooms {
+        if room.contains(spawn) {
+            continue;
+        }
+
+        let count = rng.gen_range(0..=max_per_room);
+        for _ in 0..count {
+            let pos = Position {
+                x: rng.gen_range(room.p1.x..room.p2.x),
+                y: rng.gen_range(room.p1.y..room.p2.y),
+            };
+
+            if is_free(map, pos) && !positions.contains(&pos) {
+                positions.push(pos);
+            }
+        }
+    }
+
+    positions
+}
+
+/// Picks free tiles all over the map, about one for every `tiles_per_pick` tiles of open
+/// floor, keeping away from `spawn`.
+fn scatter_positions(
+    map: &Map,
+    spawn: Position,
+    tiles_per_pick: usize,
+    rng: &mut StdRng,
+) -> Vec<Position> {
+    let open_tiles: Vec<Position> = (0..map.height)
+        .flat_map(|y| (0..map.width).map(move |x| Position { x, y }))
+        .filter(|pos| is_free(map, *pos))
+        .filter(|pos| (pos.x - spawn.x).abs().max((pos.y - spawn.y).abs()) > SAFE_RADIUS)
+        .collect();
+
+    if open_tiles.is_empty() {
+        return Vec::new();
+    }
+
+    let count = open_tiles.len() / tiles_per_pick * (1 + map.depth / 3);
+    let mut positions: Vec<Position> = Vec::new();
+
+    for _ in 0..count {
+        let pos = open_tiles[rng.gen_range(0..open_tiles.len())];
+
+        if !positions.contains(&pos) {
+            positions.push(pos);
+        }
+    }
+
+    positions
+}
+
+/// Monsters and items only go on plain floor, never on stairs or walls.
+fn is_free(map: &Map, pos: Position) -> bool {
+    map.get_tile_at(pos) == Some(&Tile::Floor)
+}
+
+pub fn spawn_monster(ecs: &mut World, kind: MonsterKind, pos: Position) -> Entity {
+    // Goblins are quicker than the player, trolls are slower but hit much harder
+    let (name, glyph, fg, speed, stats) = match kind {
+        MonsterKind::Goblin => ("Goblin", 'g', Color::Red, 120, CombatStats::new(8, 1, 3)),
+        MonsterKind::Orc => ("Orc", 'o', Color::Red, 100, CombatStats::new(16, 1, 3)),
+        MonsterKind::Troll => ("Troll", 'T', Color::Magenta, 70, CombatStats::new(30, 3, 7)),
+    };
+
+    let monster = ecs
+        .create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph,
+            fg,
+            bg: Color::Default,
+            layer: Layer::Actors,
+            attrs: Attributes::NONE,
+        })
+        .with(Viewshed::new(8))
+        .with(Monster {})
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(BlocksTile {})
+        .with(Energy::new(speed))
+        .with(stats)
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    // Orcs fight with a blade, which they drop when they die
+    if kind == MonsterKind::Orc {
+        give_item(ecs, monster, ItemKind::Dagger);
+    }
+
+    monster
+}
+
+pub fn spawn_item(ecs: &mut World, kind: ItemKind, pos: Position) -> Entity {
+    item_builder(ecs, kind)
+        .with(pos)
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Creates an item in `owner`'s backpack and, if it can be equipped, has them equip it.
+pub fn give_item(ecs: &mut World, owner: Entity, kind: ItemKind) -> Entity {
+    let item = item_builder(ecs, kind)
+        .with(InBackpack { owner })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    if ecs.read_storage::<Equippable>().contains(item) {
+        ecs.write_storage::<WantsToEquipItem>()
+            .insert(owner, WantsToEquipItem { item })
+            .expect("Unable to equip item");
+    }
+
+    item
+}
+
+/// Starts building an item of `kind`, with everything but where it is.
+fn item_builder(ecs: &mut World, kind: ItemKind) -> EntityBuilder<'_> {
+    let (name, glyph, fg) = match kind {
+        ItemKind::HealthPotion => ("Health Potion", '!', Color::Magenta),
+        ItemKind::MagicMissileScroll => ("Magic Missile Scroll", '?', Color::Cyan),
+        ItemKind::FireballScroll => ("Fireball Scroll", '?', Color::Yellow),
+        ItemKind::ConfusionScroll => ("Confusion Scroll", '?', Color::Magenta),
+        ItemKind::Dagger => ("Dagger", '/', Color::Cyan),
+        ItemKind::Longsword => ("Longsword", '/', Color::Yellow),
+        ItemKind::Shield => ("Shield", '(', Color::Cyan),
+        ItemKind::TowerShield => ("Tower Shield", '(', Color::Yellow),
+        ItemKind::LeatherArmour => ("Leather Armour", '[', Color::Cyan),
+        ItemKind::RingOfStrength => ("Ring of Strength", '=', Color::Yellow),
+    };
+
+    let builder = ecs
+        .create_entity()
+        .with(Renderable {
+            glyph,
+            fg,
+            bg: Color::Default,
+            layer: Layer::Items,
+            attrs: Attributes::NONE,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(Item {});
+
+    match kind {
+        ItemKind::HealthPotion => builder
+            .with(Consumable {})
+            .with(ProvidesHealing { heal_amount: 8 }),
+        ItemKind::MagicMissileScroll => builder
+            .with(Consumable {})
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 8 }),
+        ItemKind::FireballScroll => builder
+            .with(Consumable {})
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 20 })
+            .with(AreaOfEffect { radius: 3 }),
+        ItemKind::ConfusionScroll => builder
+            .with(Consumable {})
+            .with(Ranged { range: 6 })
+            .with(Confusion { turns: 4 }),
+        ItemKind::Dagger => builder
+            .with(Equippable { slot: EquipmentSlot::Melee })
+            .with(MeleePowerBonus { power: 2 }),
+        ItemKind::Longsword => builder
+            .with(Equippable { slot: EquipmentSlot::Melee })
+            .with(MeleePowerBonus { power: 4 }),
+        ItemKind::Shield => builder
+            .with(Equippable { slot: EquipmentSlot::Shield })
+            .with(DefenseBonus { defense: 1 }),
+        ItemKind::TowerShield => builder
+            .with(Equippable { slot: EquipmentSlot::Shield })
+            .with(DefenseBonus { defense: 3 }),
+        ItemKind::LeatherArmour => builder
+            .with(Equippable { slot: EquipmentSlot::Armour })
+            .with(DefenseBonus { defense: 1 }),
+        ItemKind::RingOfStrength => builder
+            .with(Equippable { slot: EquipmentSlot::Ring })
+            .with(MeleePowerBonus { power: 1 }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rolls `table` many times and returns which kinds came up.
+    fn rolled<K: Copy + PartialEq>(table: &SpawnTable<K>) -> Vec<K> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut seen = Vec::new();
+
+        for _ in 0..2000 {
+            let kind = table.roll(&mut rng).unwrap();
+            if !seen.contains(&kind) {
+                seen.push(kind);
+            }
+        }
+
+        seen
+    }
+
+    #[test]
+    fn empty_table_rolls_nothing() {
+        let table: SpawnTable<MonsterKind> = SpawnTable::new();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(table.roll(&mut rng), None);
+    }
+
+    #[test]
+    fn roll_follows_the_weights() {
+        let mut table = SpawnTable::new();
+        table.add(MonsterKind::Goblin, 3);
+        table.add(MonsterKind::Orc, 1);
+        table.add(MonsterKind::Troll, 0);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let goblins = (0..4000)
+            .filter(|_| table.roll(&mut rng) == Some(MonsterKind::Goblin))
+            .count();
+
+        assert!((2800..3200).contains(&goblins), "{goblins} goblins out of 4000");
+        assert!(!rolled(&table).contains(&MonsterKind::Troll));
+    }
+
+    #[test]
+    fn monsters_get_tougher_with_depth() {
+        assert_eq!(rolled(&SpawnTable::monsters_for_depth(1)), [MonsterKind::Goblin]);
+        assert!(!rolled(&SpawnTable::monsters_for_depth(3)).contains(&MonsterKind::Troll));
+        assert!(rolled(&SpawnTable::monsters_for_depth(4)).contains(&MonsterKind::Troll));
+    }
+
+    #[test]
+    fn better_items_show_up_deeper() {
+        let first = rolled(&SpawnTable::items_for_depth(1));
+        assert!(first.contains(&ItemKind::HealthPotion));
+        assert!(!first.contains(&ItemKind::FireballScroll));
+        assert!(!first.contains(&ItemKind::Longsword));
+
+        let third = rolled(&SpawnTable::items_for_depth(3));
+        assert!(third.contains(&ItemKind::FireballScroll));
+        assert!(third.contains(&ItemKind::Longsword));
+    }
+
+    #[test]
+    fn same_rng_rolls_the_same_kinds() {
+        let table = SpawnTable::items_for_depth(5);
+        let mut first = StdRng::seed_from_u64(99);
+        let mut second = StdRng::seed_from_u64(99);
+
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut first), table.roll(&mut second));
+        }
+    }
+}
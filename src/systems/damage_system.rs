@@ -0,0 +1,215 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        combat_stats::CombatStats, equipped::Equipped, in_backpack::InBackpack, name::Name,
+        position::Position, suffer_damage::SufferDamage,
+    },
+    gamelog::GameLog,
+    player::Player,
+    KillCount,
+};
+
+/// Applies the damage every entity took this turn.
+pub struct DamageSystem {}
+
+impl<'a> System<'a> for DamageSystem {
+    type SystemData = (
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+    );
+
+    fn run(&mut self, (mut stats, mut damage): Self::SystemData) {
+        for (stats, damage) in (&mut stats, &damage).join() {
+            stats.hp -= damage.amount.iter().sum::<i32>();
+        }
+
+        damage.clear();
+    }
+}
+
+/// Removes every entity that has run out of health.
+///
+/// The player is never removed, so the game-over screen still has something to show.
+/// Whatever the dead were carrying or wearing drops to the floor where they fell.
+///
+/// ## Returns
+/// `true` if the player is dead.
+pub fn delete_the_dead(ecs: &mut World) -> bool {
+    let mut dead: Vec<Entity> = Vec::new();
+    let mut player_died = false;
+
+    {
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let players = ecs.read_storage::<Player>();
+        let names = ecs.read_storage::<Name>();
+        let entities = ecs.entities();
+        let mut log = ecs.write_resource::<GameLog>();
+
+        for (entity, stats) in (&entities, &combat_stats).join() {
+            if stats.hp > 0 {
+                continue;
+            }
+
+            if players.contains(entity) {
+                player_died = true;
+            } else {
+                if let Some(name) = names.get(entity) {
+                    log.log(format!("{} dies.", name.name));
+                }
+                dead.push(entity);
+            }
+        }
+    }
+
+    drop_belongings(ecs, &dead);
+    ecs.write_resource::<KillCount>().0 += dead.len() as u64;
+
+    for victim in dead {
+        ecs.delete_entity(victim).expect("Unable to delete");
+    }
+
+    player_died
+}
+
+/// Puts every item carried or worn by one of `owners` on the floor under them. Items of
+/// owners that aren't on the current floor are deleted.
+fn drop_belongings(ecs: &mut World, owners: &[Entity]) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut equipped = ecs.write_storage::<Equipped>();
+
+    let carried = (&entities, &backpack)
+        .join()
+        .map(|(item, carried)| (item, carried.owner));
+    let worn = (&entities, &equipped)
+        .join()
+        .map(|(item, worn)| (item, worn.owner));
+
+    let belongings: Vec<(Entity, Entity)> = carried
+        .chain(worn)
+        .filter(|(_, owner)| owners.contains(owner))
+        .collect();
+
+    for (item, owner) in belongings {
+        backpack.remove(item);
+        equipped.remove(item);
+
+        match positions.get(owner).copied() {
+            Some(pos) => {
+                positions.insert(item, pos).expect("Unable to drop item");
+            }
+            None => {
+                entities.delete(item).expect("Unable to delete item");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::equippable::EquipmentSlot;
+
+    fn arena() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        ecs.insert(GameLog::new());
+        ecs.insert(KillCount::default());
+
+        ecs
+    }
+
+    /// An entity named `name` standing at `pos` with `hp` left.
+    fn fighter(ecs: &mut World, name: &str, hp: i32, pos: Position) -> Entity {
+        ecs.create_entity()
+            .with(CombatStats { hp, ..CombatStats::new(10, 0, 1) })
+            .with(Name {
+                name: name.to_string(),
+            })
+            .with(pos)
+            .build()
+    }
+
+    #[test]
+    fn damage_adds_up_over_the_turn() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", 10, Position { x: 1, y: 1 });
+        for amount in [3, 4] {
+            SufferDamage::new_damage(&mut ecs.write_storage(), orc, amount);
+        }
+
+        DamageSystem {}.run_now(&ecs);
+
+        assert_eq!(ecs.read_storage::<CombatStats>().get(orc).unwrap().hp, 3);
+        assert!(ecs.read_storage::<SufferDamage>().is_empty());
+    }
+
+    #[test]
+    fn dead_monsters_are_removed_and_counted() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", 0, Position { x: 1, y: 1 });
+        let goblin = fighter(&mut ecs, "Goblin", 2, Position { x: 2, y: 1 });
+
+        assert!(!delete_the_dead(&mut ecs));
+        ecs.maintain();
+
+        assert!(!ecs.is_alive(orc));
+        assert!(ecs.is_alive(goblin));
+        assert_eq!(ecs.fetch::<KillCount>().0, 1);
+        assert_eq!(ecs.fetch::<GameLog>().entries, ["Orc dies."]);
+    }
+
+    #[test]
+    fn dead_monsters_drop_what_they_carry() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", 0, Position { x: 4, y: 7 });
+        let potion = ecs.create_entity().with(InBackpack { owner: orc }).build();
+
+        delete_the_dead(&mut ecs);
+        ecs.maintain();
+
+        assert!(ecs.is_alive(potion));
+        assert!(!ecs.read_storage::<InBackpack>().contains(potion));
+        assert_eq!(
+            ecs.read_storage::<Position>().get(potion),
+            Some(&Position { x: 4, y: 7 })
+        );
+    }
+
+    #[test]
+    fn dead_monsters_drop_what_they_wear() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", 0, Position { x: 4, y: 7 });
+        let sword = ecs
+            .create_entity()
+            .with(Equipped {
+                owner: orc,
+                slot: EquipmentSlot::Melee,
+            })
+            .build();
+
+        delete_the_dead(&mut ecs);
+        ecs.maintain();
+
+        assert!(!ecs.read_storage::<Equipped>().contains(sword));
+        assert_eq!(
+            ecs.read_storage::<Position>().get(sword),
+            Some(&Position { x: 4, y: 7 })
+        );
+    }
+
+    #[test]
+    fn a_dead_player_is_kept() {
+        let mut ecs = arena();
+        let player = fighter(&mut ecs, "Player", -3, Position { x: 1, y: 1 });
+        ecs.write_storage::<Player>().insert(player, Player {}).unwrap();
+
+        assert!(delete_the_dead(&mut ecs));
+        ecs.maintain();
+
+        assert!(ecs.is_alive(player));
+        assert_eq!(ecs.fetch::<KillCount>().0, 0);
+    }
+}
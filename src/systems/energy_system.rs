@@ -0,0 +1,194 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{energy::Energy, monster::Monster, position::Position, viewshed::Viewshed},
+    player,
+};
+
+/// Moves game time forward by one tick, giving every entity its speed worth of energy.
+///
+/// Entities on other floors have no `Position`, so time stands still for them.
+pub struct EnergySystem {}
+
+impl<'a> System<'a> for EnergySystem {
+    type SystemData = (WriteStorage<'a, Energy>, ReadStorage<'a, Position>);
+
+    fn run(&mut self, (mut energies, positions): Self::SystemData) {
+        for (energy, _pos) in (&mut energies, &positions).join() {
+            energy.energy += energy.speed;
+        }
+    }
+}
+
+/// Moves game time forward until the player has the energy to act again.
+///
+/// Whenever a monster on the floor has the energy to act, `act` is run to let every
+/// such monster take one action, so fast monsters get it more than once. `act` returns
+/// `false` to stop the scheduler early, such as when the player has died.
+pub fn run_until_player_acts(ecs: &mut World, mut act: impl FnMut(&mut World) -> bool) {
+    loop {
+        while any_monster_ready(ecs) {
+            if !act(ecs) {
+                return;
+            }
+        }
+
+        if player::is_ready(ecs) {
+            break;
+        }
+
+        let mut energy = EnergySystem {};
+        energy.run_now(ecs);
+    }
+}
+
+/// Returns whether a monster that the `MonsterAI` system would move has the energy to act.
+///
+/// This has to join the same components as `MonsterAI`, or a monster it never moves would
+/// keep the scheduler waiting on it forever.
+fn any_monster_ready(ecs: &World) -> bool {
+    let monsters = ecs.read_storage::<Monster>();
+    let energies = ecs.read_storage::<Energy>();
+    let positions = ecs.read_storage::<Position>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+
+    // Monsters on other floors have no position and never get to act
+    (&monsters, &energies, &positions, &viewsheds)
+        .join()
+        .any(|(_monster, energy, _pos, _viewshed)| energy.can_act())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{energy::ACTION_COST, off_floor::OffFloor},
+        player::Player,
+    };
+
+    /// A floor with the player and a single monster of `speed`, both ready to act.
+    fn arena(speed: i32) -> (World, Entity) {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+
+        ecs.create_entity()
+            .with(Player {})
+            .with(Energy::new(100))
+            .with(Position { x: 1, y: 1 })
+            .build();
+        let monster = ecs
+            .create_entity()
+            .with(Monster {})
+            .with(Energy::new(speed))
+            .with(Position { x: 5, y: 5 })
+            .with(Viewshed {
+                visible_tiles: Vec::new(),
+                range: 8,
+                dirty: true,
+            })
+            .build();
+
+        (ecs, monster)
+    }
+
+    /// Has the player act `turns` times, and returns how many times the monsters acted
+    /// after each of them.
+    fn actions_per_turn(ecs: &mut World, turns: usize) -> Vec<u32> {
+        (0..turns)
+            .map(|_| {
+                for (_player, energy) in
+                    (&ecs.read_storage::<Player>(), &mut ecs.write_storage::<Energy>()).join()
+                {
+                    energy.energy -= ACTION_COST;
+                }
+
+                let mut acted = 0;
+                run_until_player_acts(ecs, |ecs| {
+                    let monsters = ecs.read_storage::<Monster>();
+                    let positions = ecs.read_storage::<Position>();
+                    let viewsheds = ecs.read_storage::<Viewshed>();
+                    for (_monster, energy, _pos, _viewshed) in (
+                        &monsters,
+                        &mut ecs.write_storage::<Energy>(),
+                        &positions,
+                        &viewsheds,
+                    )
+                        .join()
+                    {
+                        if energy.can_act() {
+                            energy.energy -= ACTION_COST;
+                            acted += 1;
+                        }
+                    }
+
+                    true
+                });
+
+                acted
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fast_monsters_act_more_than_once_a_turn() {
+        let (mut ecs, _monster) = arena(120);
+
+        let actions = actions_per_turn(&mut ecs, 10);
+
+        // The 100 energy it starts with, then 120 for every one of the player's turns
+        assert_eq!(actions.iter().sum::<u32>(), 13);
+        assert!(actions.contains(&2), "never acted twice in a turn: {actions:?}");
+        assert!(!actions.contains(&0), "missed a turn: {actions:?}");
+    }
+
+    #[test]
+    fn slow_monsters_skip_turns() {
+        let (mut ecs, _monster) = arena(70);
+
+        let actions = actions_per_turn(&mut ecs, 10);
+
+        // The 100 energy it starts with, then 70 for every one of the player's turns
+        assert_eq!(actions.iter().sum::<u32>(), 8);
+        assert!(actions.contains(&0), "never skipped a turn: {actions:?}");
+        assert!(actions.iter().all(|acted| *acted <= 1), "acted twice in a turn: {actions:?}");
+    }
+
+    #[test]
+    fn monsters_on_other_floors_never_gain_energy() {
+        let (mut ecs, monster) = arena(120);
+        ecs.write_storage::<Energy>().get_mut(monster).unwrap().energy = 0;
+        let pos = ecs.write_storage::<Position>().remove(monster).unwrap();
+        ecs.write_storage::<OffFloor>()
+            .insert(monster, OffFloor { depth: 2, pos })
+            .unwrap();
+
+        let actions = actions_per_turn(&mut ecs, 10);
+
+        assert_eq!(actions, [0; 10]);
+        assert_eq!(ecs.read_storage::<Energy>().get(monster).unwrap().energy, 0);
+    }
+
+    #[test]
+    fn monsters_that_cannot_see_do_not_hold_up_the_player() {
+        let (mut ecs, monster) = arena(120);
+        ecs.write_storage::<Viewshed>().remove(monster);
+
+        let actions = actions_per_turn(&mut ecs, 10);
+
+        assert_eq!(actions, [0; 10]);
+    }
+
+    #[test]
+    fn act_can_stop_the_scheduler_early() {
+        let (mut ecs, monster) = arena(300);
+        let mut calls = 0;
+
+        run_until_player_acts(&mut ecs, |_ecs| {
+            calls += 1;
+            false
+        });
+
+        assert_eq!(calls, 1);
+        assert_eq!(ecs.read_storage::<Energy>().get(monster).unwrap().energy, 100);
+    }
+}
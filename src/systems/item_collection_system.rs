@@ -0,0 +1,168 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        in_backpack::{InBackpack, BACKPACK_CAPACITY},
+        name::Name,
+        position::Position,
+        wants_to_pickup_item::WantsToPickupItem,
+    },
+    gamelog::GameLog,
+    player::Player,
+};
+
+/// Moves every item someone asked to pick up off the floor and into their backpack.
+///
+/// Only items still lying on the collector's tile are picked up, and never more than
+/// `BACKPACK_CAPACITY` items end up in one backpack.
+pub struct ItemCollectionSystem {}
+
+impl<'a> System<'a> for ItemCollectionSystem {
+    type SystemData = (
+        WriteStorage<'a, WantsToPickupItem>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Player>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (mut wants_pickup, mut positions, mut backpack, names, players, mut log): Self::SystemData,
+    ) {
+        for pickup in wants_pickup.join() {
+            let is_player = players.contains(pickup.collected_by);
+
+            // Someone else may have got to it first, or the collector moved away
+            let within_reach = match (positions.get(pickup.item), positions.get(pickup.collected_by)) {
+                (Some(item_pos), Some(collector_pos)) => item_pos == collector_pos,
+                _ => false,
+            };
+            if !within_reach {
+                if is_player {
+                    log.log("There is nothing here to pick up.");
+                }
+                continue;
+            }
+
+            let carried = backpack
+                .join()
+                .filter(|carried| carried.owner == pickup.collected_by)
+                .count();
+            if carried >= BACKPACK_CAPACITY {
+                if is_player {
+                    log.log("Your pack is full.");
+                }
+                continue;
+            }
+
+            positions.remove(pickup.item);
+            backpack
+                .insert(
+                    pickup.item,
+                    InBackpack {
+                        owner: pickup.collected_by,
+                    },
+                )
+                .expect("Unable to put item in backpack");
+
+            if is_player {
+                let item_name = names.get(pickup.item).map_or("something", |n| n.name.as_str());
+                log.log(format!("You pick up the {item_name}."));
+            }
+        }
+
+        wants_pickup.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::item::Item;
+
+    fn arena() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        ecs.insert(GameLog::new());
+
+        ecs
+    }
+
+    fn goblin(ecs: &mut World, pos: Position) -> Entity {
+        ecs.create_entity().with(pos).build()
+    }
+
+    fn item_at(ecs: &mut World, pos: Position) -> Entity {
+        ecs.create_entity().with(Item {}).with(pos).build()
+    }
+
+    /// Has `collector` try to pick up `item`, and returns whether it ended up carrying it.
+    fn pick_up(ecs: &mut World, collector: Entity, item: Entity) -> bool {
+        ecs.write_storage::<WantsToPickupItem>()
+            .insert(
+                collector,
+                WantsToPickupItem {
+                    collected_by: collector,
+                    item,
+                },
+            )
+            .unwrap();
+        ItemCollectionSystem {}.run_now(ecs);
+
+        ecs.read_storage::<InBackpack>()
+            .get(item)
+            .is_some_and(|carried| carried.owner == collector)
+    }
+
+    #[test]
+    fn items_on_the_same_tile_are_picked_up() {
+        let mut ecs = arena();
+        let pos = Position { x: 3, y: 4 };
+        let goblin = goblin(&mut ecs, pos);
+        let item = item_at(&mut ecs, pos);
+
+        assert!(pick_up(&mut ecs, goblin, item));
+        assert!(!ecs.read_storage::<Position>().contains(item));
+    }
+
+    #[test]
+    fn items_out_of_reach_stay_where_they_are() {
+        let mut ecs = arena();
+        let goblin = goblin(&mut ecs, Position { x: 3, y: 4 });
+        let item = item_at(&mut ecs, Position { x: 5, y: 4 });
+
+        assert!(!pick_up(&mut ecs, goblin, item));
+        assert_eq!(ecs.read_storage::<Position>().get(item), Some(&Position { x: 5, y: 4 }));
+    }
+
+    #[test]
+    fn items_someone_already_holds_are_not_taken() {
+        let mut ecs = arena();
+        let pos = Position { x: 3, y: 4 };
+        let first = goblin(&mut ecs, pos);
+        let second = goblin(&mut ecs, pos);
+        let item = item_at(&mut ecs, pos);
+
+        assert!(pick_up(&mut ecs, first, item));
+        assert!(!pick_up(&mut ecs, second, item));
+        assert_eq!(ecs.read_storage::<InBackpack>().get(item).unwrap().owner, first);
+    }
+
+    #[test]
+    fn a_full_pack_takes_nothing_more() {
+        let mut ecs = arena();
+        let pos = Position { x: 3, y: 4 };
+        let goblin = goblin(&mut ecs, pos);
+        for _ in 0..BACKPACK_CAPACITY {
+            let item = item_at(&mut ecs, pos);
+            assert!(pick_up(&mut ecs, goblin, item));
+        }
+
+        let one_too_many = item_at(&mut ecs, pos);
+
+        assert!(!pick_up(&mut ecs, goblin, one_too_many));
+        assert!(ecs.read_storage::<Position>().contains(one_too_many));
+    }
+}
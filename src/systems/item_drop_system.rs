@@ -0,0 +1,124 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        in_backpack::InBackpack, name::Name, position::Position,
+        wants_to_drop_item::WantsToDropItem,
+    },
+    gamelog::GameLog,
+    player::Player,
+};
+
+/// Puts every item someone asked to drop on the floor where they stand.
+///
+/// Only items in the dropper's own backpack are dropped.
+pub struct ItemDropSystem {}
+
+impl<'a> System<'a> for ItemDropSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToDropItem>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Player>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut wants_drop, mut positions, mut backpack, names, players, mut log): Self::SystemData,
+    ) {
+        for (entity, drop) in (&entities, &wants_drop).join() {
+            // Someone without a position has nowhere to put the item down
+            let Some(pos) = positions.get(entity).copied() else {
+                continue;
+            };
+
+            // The item may have been used up, or was never theirs to drop
+            let theirs = backpack.get(drop.item).is_some_and(|carried| carried.owner == entity);
+            if !theirs {
+                continue;
+            }
+
+            let Ok(_) = positions.insert(drop.item, pos) else {
+                continue;
+            };
+            backpack.remove(drop.item);
+
+            if players.contains(entity) {
+                let item_name = names.get(drop.item).map_or("something", |n| n.name.as_str());
+                log.log(format!("You drop the {item_name}."));
+            }
+        }
+
+        wants_drop.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::item::Item;
+
+    fn arena() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        ecs.insert(GameLog::new());
+
+        ecs
+    }
+
+    fn goblin(ecs: &mut World, pos: Position) -> Entity {
+        ecs.create_entity().with(pos).build()
+    }
+
+    fn item_held_by(ecs: &mut World, owner: Entity) -> Entity {
+        ecs.create_entity().with(Item {}).with(InBackpack { owner }).build()
+    }
+
+    /// Has `dropper` try to drop `item`, and returns where it ended up on the floor.
+    fn try_drop(ecs: &mut World, dropper: Entity, item: Entity) -> Option<Position> {
+        ecs.write_storage::<WantsToDropItem>()
+            .insert(dropper, WantsToDropItem { item })
+            .unwrap();
+        ItemDropSystem {}.run_now(ecs);
+
+        ecs.read_storage::<Position>().get(item).copied()
+    }
+
+    #[test]
+    fn carried_items_land_at_the_droppers_feet() {
+        let mut ecs = arena();
+        let pos = Position { x: 3, y: 4 };
+        let goblin = goblin(&mut ecs, pos);
+        let item = item_held_by(&mut ecs, goblin);
+
+        assert_eq!(try_drop(&mut ecs, goblin, item), Some(pos));
+        assert!(!ecs.read_storage::<InBackpack>().contains(item));
+    }
+
+    #[test]
+    fn items_someone_else_holds_stay_with_them() {
+        let mut ecs = arena();
+        let owner = goblin(&mut ecs, Position { x: 3, y: 4 });
+        let thief = goblin(&mut ecs, Position { x: 5, y: 4 });
+        let item = item_held_by(&mut ecs, owner);
+
+        assert_eq!(try_drop(&mut ecs, thief, item), None);
+        assert_eq!(ecs.read_storage::<InBackpack>().get(item).unwrap().owner, owner);
+    }
+
+    #[test]
+    fn items_on_the_floor_are_not_moved() {
+        let mut ecs = arena();
+        let goblin = goblin(&mut ecs, Position { x: 3, y: 4 });
+        let item = ecs
+            .create_entity()
+            .with(Item {})
+            .with(Position { x: 7, y: 7 })
+            .build();
+
+        assert_eq!(try_drop(&mut ecs, goblin, item), Some(Position { x: 7, y: 7 }));
+    }
+}
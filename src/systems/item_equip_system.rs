@@ -0,0 +1,108 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        equippable::Equippable, equipped::Equipped, in_backpack::InBackpack, name::Name,
+        wants_to_equip_item::WantsToEquipItem, wants_to_unequip_item::WantsToUnequipItem,
+    },
+    gamelog::GameLog,
+    player::Player,
+};
+
+/// Equips and takes off items for anyone who asked to, player and monsters alike.
+///
+/// Equipping an item into a slot that is already taken puts the old item back in the
+/// backpack first.
+pub struct ItemEquipSystem {}
+
+impl<'a> System<'a> for ItemEquipSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToEquipItem>,
+        WriteStorage<'a, WantsToUnequipItem>,
+        ReadStorage<'a, Equippable>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Player>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut wants_equip,
+            mut wants_unequip,
+            equippables,
+            mut equipped,
+            mut backpack,
+            names,
+            players,
+            mut log,
+        ): Self::SystemData,
+    ) {
+        let item_name = |item: Entity| names.get(item).map_or("something", |n| n.name.as_str());
+
+        for (entity, unequip) in (&entities, &wants_unequip).join() {
+            if equipped
+                .get(unequip.item)
+                .is_none_or(|worn| worn.owner != entity)
+            {
+                continue;
+            }
+
+            equipped.remove(unequip.item);
+            backpack
+                .insert(unequip.item, InBackpack { owner: entity })
+                .expect("Unable to put item in backpack");
+
+            if players.contains(entity) {
+                log.log(format!("You take off the {}.", item_name(unequip.item)));
+            }
+        }
+
+        wants_unequip.clear();
+
+        for (entity, equip) in (&entities, &wants_equip).join() {
+            let Some(equippable) = equippables.get(equip.item) else {
+                continue;
+            };
+            let slot = equippable.slot;
+
+            let taken: Vec<Entity> = (&entities, &equipped)
+                .join()
+                .filter(|(_, worn)| worn.owner == entity && worn.slot == slot)
+                .map(|(item, _)| item)
+                .collect();
+
+            for item in taken {
+                equipped.remove(item);
+                backpack
+                    .insert(item, InBackpack { owner: entity })
+                    .expect("Unable to put item in backpack");
+
+                if players.contains(entity) {
+                    log.log(format!("You take off the {}.", item_name(item)));
+                }
+            }
+
+            backpack.remove(equip.item);
+            equipped
+                .insert(
+                    equip.item,
+                    Equipped {
+                        owner: entity,
+                        slot,
+                    },
+                )
+                .expect("Unable to equip item");
+
+            if players.contains(entity) {
+                log.log(format!("You equip the {}.", item_name(equip.item)));
+            }
+        }
+
+        wants_equip.clear();
+    }
+}
@@ -0,0 +1,265 @@
+use bracket_lib::prelude::{field_of_view, Point};
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        area_of_effect::AreaOfEffect, combat_stats::CombatStats, confusion::Confusion,
+        consumable::Consumable, inflicts_damage::InflictsDamage, monster::Monster, name::Name,
+        position::Position, provides_healing::ProvidesHealing, suffer_damage::SufferDamage,
+        wants_to_use_item::WantsToUseItem,
+    },
+    gamelog::GameLog,
+    map::Map,
+    player::Player,
+};
+
+/// Applies the effects of every item someone asked to use, and uses up the consumables.
+///
+/// Items without a target affect whoever uses them. Ranged items affect what stands on
+/// their target tile, or on every tile their area of effect reaches from it.
+pub struct ItemUseSystem {}
+
+impl<'a> System<'a> for ItemUseSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, Consumable>,
+        ReadStorage<'a, ProvidesHealing>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, AreaOfEffect>,
+        WriteStorage<'a, Confusion>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Player>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            map,
+            entities,
+            mut wants_use,
+            consumables,
+            healing,
+            inflicts_damage,
+            area_of_effect,
+            mut confusion,
+            mut combat_stats,
+            mut suffer_damage,
+            monsters,
+            names,
+            players,
+            mut log,
+        ): Self::SystemData,
+    ) {
+        for (entity, use_item) in (&entities, &wants_use).join() {
+            let item_name = names.get(use_item.item).map_or("something", |n| n.name.as_str());
+            let is_player = players.contains(entity);
+
+            let targets: Vec<Entity> = match use_item.target {
+                None => vec![entity],
+                Some(target) => ranged_targets(
+                    &map,
+                    area_of_effect.get(use_item.item),
+                    target,
+                    entity,
+                    |target| combat_stats.contains(target),
+                ),
+            };
+
+            // Aimed at nothing, the item is kept for a better use. The targeting screen
+            // never lets the player aim like that, so it can't cost them a turn
+            if targets.is_empty() {
+                if is_player {
+                    log.log("Nothing is there.");
+                }
+                continue;
+            }
+
+            if let Some(healer) = healing.get(use_item.item) {
+                for target in &targets {
+                    let Some(stats) = combat_stats.get_mut(*target) else {
+                        continue;
+                    };
+
+                    let healed = healer.heal_amount.min(stats.max_hp - stats.hp).max(0);
+                    stats.hp += healed;
+
+                    if is_player && *target == entity {
+                        log.log(format!("You use the {item_name}, healing {healed} hp."));
+                    }
+                }
+            }
+
+            if let Some(damage) = inflicts_damage.get(use_item.item) {
+                for target in &targets {
+                    if !combat_stats.contains(*target) {
+                        continue;
+                    }
+
+                    SufferDamage::new_damage(&mut suffer_damage, *target, damage.damage);
+
+                    if is_player && *target == entity {
+                        log.log(format!("The {item_name} hits you for {} hp.", damage.damage));
+                    } else if is_player {
+                        let target_name = names.get(*target).map_or("something", |n| n.name.as_str());
+                        log.log(format!(
+                            "The {item_name} hits {target_name} for {} hp.",
+                            damage.damage
+                        ));
+                    }
+                }
+            }
+
+            if let Some(confuses) = confusion.get(use_item.item).cloned() {
+                for target in &targets {
+                    if !monsters.contains(*target) {
+                        continue;
+                    }
+
+                    confusion
+                        .insert(*target, confuses.clone())
+                        .expect("Unable to confuse target");
+
+                    if is_player {
+                        let target_name = names.get(*target).map_or("something", |n| n.name.as_str());
+                        log.log(format!("The {item_name} confuses {target_name}."));
+                    }
+                }
+            }
+
+            if consumables.contains(use_item.item) {
+                entities.delete(use_item.item).expect("Unable to delete item");
+            }
+        }
+
+        wants_use.clear();
+    }
+}
+
+/// Returns what a ranged item used by `user` and aimed at `target` would hit: whatever
+/// `can_be_hit` on the target tile or, for items with an `area`, on every tile the blast
+/// reaches from it.
+///
+/// Blasts hit whoever stands in them, their user included, but an item aimed at a single
+/// tile never hits the one using it.
+pub fn ranged_targets(
+    map: &Map,
+    area: Option<&AreaOfEffect>,
+    target: Position,
+    user: Entity,
+    can_be_hit: impl Fn(Entity) -> bool,
+) -> Vec<Entity> {
+    let hit: Vec<Entity> = match area {
+        None => map
+            .content_at(target)
+            .iter()
+            .copied()
+            .filter(|entity| *entity != user)
+            .collect(),
+        // The blast fills the space around the target but doesn't go through walls
+        Some(area) => field_of_view(Point::new(target.x, target.y), area.radius, map)
+            .into_iter()
+            .map(|p| Position {
+                x: p.x as isize,
+                y: p.y as isize,
+            })
+            .flat_map(|pos| map.content_at(pos).to_vec())
+            .collect(),
+    };
+
+    // Items lying on the floor are not something to aim at
+    hit.into_iter().filter(|entity| can_be_hit(*entity)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::Tile, systems::map_indexing_system::MapIndexingSystem};
+
+    fn arena() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        ecs.insert(GameLog::new());
+        ecs.insert(Map::filled(20, 20, Tile::Floor, 0));
+
+        ecs
+    }
+
+    fn fighter(ecs: &mut World, name: &str, pos: Position) -> Entity {
+        ecs.create_entity()
+            .with(pos)
+            .with(CombatStats::new(20, 0, 5))
+            .with(Name {
+                name: name.to_string(),
+            })
+            .build()
+    }
+
+    fn scroll(ecs: &mut World, name: &str, damage: i32) -> Entity {
+        ecs.create_entity()
+            .with(Consumable {})
+            .with(InflictsDamage { damage })
+            .with(Name {
+                name: name.to_string(),
+            })
+            .build()
+    }
+
+    /// Has `user` use `item` on `target`, after indexing what stands where.
+    fn use_on(ecs: &mut World, user: Entity, item: Entity, target: Position) {
+        ecs.write_storage::<WantsToUseItem>()
+            .insert(
+                user,
+                WantsToUseItem {
+                    item,
+                    target: Some(target),
+                },
+            )
+            .unwrap();
+        MapIndexingSystem {}.run_now(ecs);
+        ItemUseSystem {}.run_now(ecs);
+        ecs.maintain();
+    }
+
+    fn last_message(ecs: &World) -> String {
+        ecs.fetch::<GameLog>().entries.last().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn a_scroll_aimed_at_an_empty_tile_is_kept() {
+        let mut ecs = arena();
+        let player = fighter(&mut ecs, "Player", Position { x: 2, y: 2 });
+        ecs.write_storage::<Player>().insert(player, Player {}).unwrap();
+        let missile = scroll(&mut ecs, "Magic Missile Scroll", 8);
+
+        use_on(&mut ecs, player, missile, Position { x: 6, y: 2 });
+
+        assert!(ecs.is_alive(missile));
+        assert_eq!(last_message(&ecs), "Nothing is there.");
+        assert!(ecs.read_storage::<WantsToUseItem>().is_empty());
+    }
+
+    #[test]
+    fn a_fireball_around_the_player_hits_you() {
+        let mut ecs = arena();
+        let player = fighter(&mut ecs, "Player", Position { x: 2, y: 2 });
+        ecs.write_storage::<Player>().insert(player, Player {}).unwrap();
+        fighter(&mut ecs, "Orc", Position { x: 3, y: 2 });
+        let fireball = scroll(&mut ecs, "Fireball Scroll", 20);
+        ecs.write_storage::<AreaOfEffect>()
+            .insert(fireball, AreaOfEffect { radius: 2 })
+            .unwrap();
+
+        use_on(&mut ecs, player, fireball, Position { x: 3, y: 2 });
+
+        let log = ecs.fetch::<GameLog>().entries.clone();
+        assert!(log.contains(&"The Fireball Scroll hits you for 20 hp.".to_string()), "{log:?}");
+        assert!(log.contains(&"The Fireball Scroll hits Orc for 20 hp.".to_string()), "{log:?}");
+        assert!(!ecs.is_alive(fireball));
+    }
+}
@@ -0,0 +1,32 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{blocks_tile::BlocksTile, position::Position},
+    map::Map,
+};
+
+/// Rebuilds the map's blocked tiles from the terrain and every entity that blocks movement,
+/// and records which entities stand on each tile.
+pub struct MapIndexingSystem {}
+
+impl<'a> System<'a> for MapIndexingSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, BlocksTile>,
+    );
+
+    fn run(&mut self, (mut map, entities, positions, blockers): Self::SystemData) {
+        map.populate_blocked();
+        map.clear_content_index();
+
+        for (entity, pos) in (&entities, &positions).join() {
+            if blockers.contains(entity) {
+                map.set_blocked(*pos, true);
+            }
+
+            map.add_content(*pos, entity);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        combat_stats::CombatStats, defense_bonus::DefenseBonus, equipped::Equipped,
+        melee_power_bonus::MeleePowerBonus, name::Name, suffer_damage::SufferDamage,
+        wants_to_melee::WantsToMelee,
+    },
+    gamelog::GameLog,
+};
+
+/// Resolves every queued melee attack.
+///
+/// Damage is the attacker's power minus the target's defense, both counting the bonuses
+/// of their equipment. Dead attackers don't get to swing, and attacks on targets that are
+/// already dead are dropped.
+pub struct MeleeCombatSystem {}
+
+impl<'a> System<'a> for MeleeCombatSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToMelee>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, MeleePowerBonus>,
+        ReadStorage<'a, DefenseBonus>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut wants_melee,
+            combat_stats,
+            mut inflict_damage,
+            names,
+            mut log,
+            equipped,
+            power_bonuses,
+            defense_bonuses,
+        ): Self::SystemData,
+    ) {
+        for (entity, wants_melee, stats, name) in
+            (&entities, &wants_melee, &combat_stats, &names).join()
+        {
+            if stats.hp <= 0 {
+                continue;
+            }
+
+            let Some(target_stats) = combat_stats.get(wants_melee.target) else {
+                continue;
+            };
+
+            if target_stats.hp <= 0 {
+                continue;
+            }
+
+            let target_name = names
+                .get(wants_melee.target)
+                .map_or("something", |target| target.name.as_str());
+
+            let power = stats.power + power_bonus(entity, &equipped, &power_bonuses);
+            let defense = target_stats.defense
+                + defense_bonus(wants_melee.target, &equipped, &defense_bonuses);
+            let damage = i32::max(0, power - defense);
+            if damage == 0 {
+                log.log(format!("{} is unable to hurt {}.", name.name, target_name));
+            } else {
+                log.log(format!(
+                    "{} hits {} for {} hp.",
+                    name.name, target_name, damage
+                ));
+                SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+            }
+        }
+
+        wants_melee.clear();
+    }
+}
+
+/// Adds up the power bonuses of everything `owner` has equipped.
+pub fn power_bonus(
+    owner: Entity,
+    equipped: &ReadStorage<Equipped>,
+    bonuses: &ReadStorage<MeleePowerBonus>,
+) -> i32 {
+    (equipped, bonuses)
+        .join()
+        .filter(|(worn, _)| worn.owner == owner)
+        .map(|(_, bonus)| bonus.power)
+        .sum()
+}
+
+/// Adds up the defense bonuses of everything `owner` has equipped.
+pub fn defense_bonus(
+    owner: Entity,
+    equipped: &ReadStorage<Equipped>,
+    bonuses: &ReadStorage<DefenseBonus>,
+) -> i32 {
+    (equipped, bonuses)
+        .join()
+        .filter(|(worn, _)| worn.owner == owner)
+        .map(|(_, bonus)| bonus.defense)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::equippable::EquipmentSlot, systems::damage_system::DamageSystem};
+
+    fn arena() -> World {
+        let mut ecs = World::new();
+        crate::register_components(&mut ecs);
+        ecs.insert(GameLog::new());
+
+        ecs
+    }
+
+    fn fighter(ecs: &mut World, name: &str, stats: CombatStats) -> Entity {
+        ecs.create_entity()
+            .with(stats)
+            .with(Name {
+                name: name.to_string(),
+            })
+            .build()
+    }
+
+    /// Starts building an item that `owner` has equipped in `slot`.
+    fn wear(ecs: &mut World, owner: Entity, slot: EquipmentSlot) -> EntityBuilder<'_> {
+        ecs.create_entity().with(Equipped { owner, slot })
+    }
+
+    /// Has `attacker` hit `target` once, and returns the target's health afterwards.
+    fn attack(ecs: &mut World, attacker: Entity, target: Entity) -> i32 {
+        ecs.write_storage::<WantsToMelee>()
+            .insert(attacker, WantsToMelee { target })
+            .unwrap();
+        MeleeCombatSystem {}.run_now(ecs);
+        DamageSystem {}.run_now(ecs);
+
+        ecs.read_storage::<CombatStats>().get(target).unwrap().hp
+    }
+
+    #[test]
+    fn damage_is_power_minus_defense() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", CombatStats::new(10, 0, 5));
+        let player = fighter(&mut ecs, "Player", CombatStats::new(10, 2, 5));
+
+        assert_eq!(attack(&mut ecs, orc, player), 7);
+        assert_eq!(attack(&mut ecs, orc, player), 4);
+        assert_eq!(
+            ecs.fetch::<GameLog>().entries.last().unwrap(),
+            "Orc hits Player for 3 hp."
+        );
+    }
+
+    #[test]
+    fn equipment_counts_towards_power_and_defense() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", CombatStats::new(10, 0, 5));
+        let player = fighter(&mut ecs, "Player", CombatStats::new(20, 2, 5));
+
+        wear(&mut ecs, orc, EquipmentSlot::Melee)
+            .with(MeleePowerBonus { power: 4 })
+            .build();
+        wear(&mut ecs, player, EquipmentSlot::Shield)
+            .with(DefenseBonus { defense: 1 })
+            .build();
+        wear(&mut ecs, player, EquipmentSlot::Armour)
+            .with(DefenseBonus { defense: 2 })
+            .build();
+        // Bonuses only count for whoever is wearing them
+        wear(&mut ecs, player, EquipmentSlot::Melee)
+            .with(MeleePowerBonus { power: 10 })
+            .build();
+
+        // (5 + 4) - (2 + 1 + 2)
+        assert_eq!(attack(&mut ecs, orc, player), 16);
+        // 5 + 10 against the orc's bare 0
+        assert_eq!(attack(&mut ecs, player, orc), -5);
+    }
+
+    #[test]
+    fn defense_above_power_does_no_harm() {
+        let mut ecs = arena();
+        let goblin = fighter(&mut ecs, "Goblin", CombatStats::new(10, 0, 2));
+        let player = fighter(&mut ecs, "Player", CombatStats::new(10, 4, 5));
+
+        assert_eq!(attack(&mut ecs, goblin, player), 10);
+        assert_eq!(
+            ecs.fetch::<GameLog>().entries.last().unwrap(),
+            "Goblin is unable to hurt Player."
+        );
+    }
+
+    #[test]
+    fn the_dead_neither_hit_nor_get_hit() {
+        let mut ecs = arena();
+        let orc = fighter(&mut ecs, "Orc", CombatStats::new(10, 0, 5));
+        let player = fighter(&mut ecs, "Player", CombatStats::new(10, 0, 5));
+
+        ecs.write_storage::<CombatStats>().get_mut(orc).unwrap().hp = 0;
+        assert_eq!(attack(&mut ecs, orc, player), 10);
+
+        assert_eq!(attack(&mut ecs, player, orc), 0);
+        assert!(ecs.fetch::<GameLog>().entries.is_empty());
+    }
+}
@@ -0,0 +1,10 @@
+pub mod damage_system;
+pub mod energy_system;
+pub mod item_collection_system;
+pub mod item_drop_system;
+pub mod item_equip_system;
+pub mod item_use_system;
+pub mod map_indexing_system;
+pub mod melee_combat_system;
+pub mod monster_ai_system;
+pub mod visibility_system;
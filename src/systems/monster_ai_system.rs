@@ -0,0 +1,130 @@
+use bracket_lib::prelude::{a_star_search, Algorithm2D, DistanceAlg, Point};
+use specs::prelude::*;
+
+use crate::{
+    components::{
+        confusion::Confusion,
+        energy::{Energy, ACTION_COST},
+        monster::Monster,
+        position::Position,
+        viewshed::Viewshed,
+        wants_to_melee::WantsToMelee,
+    },
+    map::Map,
+    player::Player,
+};
+
+/// Moves monsters towards the player.
+///
+/// A monster stays idle until the player is inside its viewshed, then follows the
+/// shortest walkable path to them. Once next to the player, monsters attack instead.
+/// Confused monsters do nothing until their confusion wears off.
+///
+/// Only monsters with enough energy act, and every action, idling included, costs
+/// `ACTION_COST`. Running the system again lets fast monsters act a second time.
+pub struct MonsterAI {}
+
+impl<'a> System<'a> for MonsterAI {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        Entities<'a>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Monster>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Player>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, WantsToMelee>,
+        WriteStorage<'a, Confusion>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut map,
+            entities,
+            mut viewsheds,
+            monsters,
+            mut positions,
+            players,
+            mut energies,
+            mut wants_melee,
+            mut confusion,
+        ): Self::SystemData,
+    ) {
+        let player = (&entities, &positions, &players)
+            .join()
+            .map(|(entity, pos, _player)| (entity, *pos))
+            .next();
+
+        for (entity, viewshed, _monster, pos, energy) in (
+            &entities,
+            &mut viewsheds,
+            &monsters,
+            &mut positions,
+            &mut energies,
+        )
+            .join()
+        {
+            if !energy.can_act() {
+                continue;
+            }
+            energy.energy -= ACTION_COST;
+
+            if let Some(confused) = confusion.get_mut(entity) {
+                confused.turns -= 1;
+                if confused.turns <= 0 {
+                    confusion.remove(entity);
+                }
+                continue;
+            }
+
+            let Some((player_entity, player_pos)) = player else {
+                continue;
+            };
+
+            if !viewshed.visible_tiles.contains(&player_pos) {
+                continue;
+            }
+
+            // Monsters attack diagonally too, unless a wall corner is in the way
+            let distance = DistanceAlg::Chebyshev.distance2d(
+                Point::new(pos.x, pos.y),
+                Point::new(player_pos.x, player_pos.y),
+            );
+            if distance <= 1.0 && map.can_step(*pos, player_pos) {
+                wants_melee
+                    .insert(
+                        entity,
+                        WantsToMelee {
+                            target: player_entity,
+                        },
+                    )
+                    .expect("Unable to insert attack");
+                continue;
+            }
+
+            let (Some(start), Some(end)) = (map.idx(*pos), map.idx(player_pos)) else {
+                continue;
+            };
+
+            let path = a_star_search(start, end, &*map);
+            if !path.success || path.steps.len() < 2 {
+                continue;
+            }
+
+            let next = map.index_to_point2d(path.steps[1]);
+            let next = Position {
+                x: next.x as isize,
+                y: next.y as isize,
+            };
+
+            // Keep the blocked tiles up to date so monsters moving later this turn
+            // don't step into the same tile
+            map.set_blocked(*pos, false);
+            map.set_blocked(next, true);
+
+            *pos = next;
+            viewshed.dirty = true;
+        }
+    }
+}
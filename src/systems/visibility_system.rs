@@ -0,0 +1,49 @@
+use bracket_lib::prelude::{field_of_view, Point};
+use specs::prelude::*;
+
+use crate::{
+    components::{position::Position, viewshed::Viewshed},
+    map::Map,
+    player::Player,
+};
+
+/// Recomputes the field of view of every entity whose viewshed is dirty.
+///
+/// When the entity is the player, the map's visible tiles are replaced with the new view
+/// and every tile in it is remembered as revealed.
+pub struct VisibilitySystem {}
+
+impl<'a> System<'a> for VisibilitySystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Player>,
+    );
+
+    fn run(&mut self, (mut map, mut viewsheds, positions, players): Self::SystemData) {
+        for (viewshed, pos, player) in (&mut viewsheds, &positions, players.maybe()).join() {
+            if !viewshed.dirty {
+                continue;
+            }
+            viewshed.dirty = false;
+
+            viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map)
+                .into_iter()
+                .map(|p| Position {
+                    x: p.x as isize,
+                    y: p.y as isize,
+                })
+                .filter(|p| map.in_bounds(*p))
+                .collect();
+
+            if player.is_some() {
+                map.clear_visible();
+
+                for tile in &viewshed.visible_tiles {
+                    map.reveal_tile(*tile);
+                }
+            }
+        }
+    }
+}
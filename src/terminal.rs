@@ -0,0 +1,235 @@
+use std::{
+    io::{stdout, Write},
+    panic,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        DisableFocusChange, EnableFocusChange, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    terminal, QueueableCommand,
+};
+
+/// Set while the terminal is in the game's mode, so it is only ever restored once.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Set by `SIGINT` and `SIGTERM`, until the game loop sees it.
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set by `SIGTSTP`, until the game loop sees it.
+static SUSPEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set by `SIGCONT`, until the game loop sees it.
+static RESUMED: AtomicBool = AtomicBool::new(false);
+/// Set once the terminal has agreed to report key releases, so it is asked again every
+/// time the game's mode is entered, and told to stop whenever it is left.
+static REPORTS_RELEASES: AtomicBool = AtomicBool::new(false);
+
+/// Keeps the terminal in the game's mode for as long as it lives: the alternate screen,
+/// a hidden cursor, no line wrap and raw input.
+///
+/// The terminal is put back the way it was when the guard is dropped, and also if the
+/// game panics or is told to stop by a signal, so it is never left unusable.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    /// Switches the terminal to the game's mode, and installs the panic hook and signal
+    /// handlers that switch it back.
+    pub fn new() -> Self {
+        install_panic_hook();
+        #[cfg(unix)]
+        install_signal_handlers();
+
+        enter();
+
+        TerminalGuard { _private: () }
+    }
+}
+
+impl Default for TerminalGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Puts the terminal in the game's mode.
+fn enter() {
+    let mut stdout = stdout();
+
+    // Nothing can be done about a terminal that refuses these, so the errors are ignored
+    let _ = stdout.queue(terminal::EnterAlternateScreen);
+    let _ = stdout.queue(cursor::Hide);
+    let _ = stdout.queue(terminal::DisableLineWrap);
+    // Keys let go of while another window has the focus never report their release
+    let _ = stdout.queue(EnableFocusChange);
+    if REPORTS_RELEASES.load(Ordering::SeqCst) {
+        let _ = stdout.queue(PushKeyboardEnhancementFlags(
+            KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+        ));
+    }
+    let _ = stdout.flush();
+
+    // Keys have to reach the game as they are pressed, not a line at a time
+    let _ = terminal::enable_raw_mode();
+
+    ACTIVE.store(true, Ordering::SeqCst);
+}
+
+/// Puts the terminal back the way it was before the game started.
+///
+/// Does nothing if it already is, so it is safe to call from every path out of the game.
+pub fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    let _ = terminal::disable_raw_mode();
+
+    let mut stdout = stdout();
+
+    // The flags belong to the alternate screen, so they have to go before leaving it
+    if REPORTS_RELEASES.load(Ordering::SeqCst) {
+        let _ = stdout.queue(PopKeyboardEnhancementFlags);
+    }
+    let _ = stdout.queue(DisableFocusChange);
+    let _ = stdout.queue(terminal::EnableLineWrap);
+    let _ = stdout.queue(cursor::Show);
+    // Leave the alternate screen, restoring the original terminal
+    let _ = stdout.queue(terminal::LeaveAlternateScreen);
+    let _ = stdout.flush();
+}
+
+/// Asks the terminal to report key releases and repeats as well as presses, if it
+/// supports the keyboard enhancement protocol.
+///
+/// Has to be called while the terminal is in the game's mode. The request is undone by
+/// `restore`, and made again whenever the game's mode is entered after that.
+///
+/// ## Returns
+/// `true` if the terminal will report key releases from now on.
+pub fn enable_key_releases() -> bool {
+    if REPORTS_RELEASES.load(Ordering::SeqCst) {
+        return true;
+    }
+
+    if !terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        return false;
+    }
+
+    let mut stdout = stdout();
+    let _ = stdout.queue(PushKeyboardEnhancementFlags(
+        KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+    ));
+    let _ = stdout.flush();
+
+    REPORTS_RELEASES.store(true, Ordering::SeqCst);
+
+    true
+}
+
+/// Restores the terminal before the panic message is printed, so it shows up on the
+/// normal screen where it can be read.
+///
+/// Only panics on the thread that installed the hook, the game loop's, restore the
+/// terminal. A panic on another thread, such as the input reader, leaves the game loop
+/// running, and it shuts down through the `TerminalGuard` once it notices. To check this
+/// by hand, add a `panic!` to the reader thread in `InputHandler::start`: the game should
+/// exit to a working shell with the panic message printed on it, not keep drawing on a
+/// restored terminal.
+fn install_panic_hook() {
+    let previous = panic::take_hook();
+    let game_loop = thread::current().id();
+
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().id() == game_loop {
+            restore();
+        }
+        previous(info);
+    }));
+}
+
+/// Listens for the signals that stop, pause or resume the game on a thread of its own,
+/// and passes them on to the game loop.
+///
+/// The terminal itself is only touched from the game loop, so a signal never arrives in
+/// the middle of a frame being drawn.
+#[cfg(unix)]
+fn install_signal_handlers() {
+    use signal_hook::{
+        consts::{SIGCONT, SIGINT, SIGTERM, SIGTSTP},
+        iterator::Signals,
+    };
+
+    // Without the handlers the signals keep their default behaviour, which still works,
+    // it just leaves the terminal as it is
+    let Ok(mut signals) = Signals::new([SIGINT, SIGTERM, SIGTSTP, SIGCONT]) else {
+        return;
+    };
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let flag = match signal {
+                SIGTSTP => &SUSPEND_REQUESTED,
+                SIGCONT => &RESUMED,
+                _ => &QUIT_REQUESTED,
+            };
+
+            flag.store(true, Ordering::SeqCst);
+        }
+    });
+}
+
+/// Returns whether the game was asked to quit by a signal since the last call.
+pub fn take_quit_request() -> bool {
+    QUIT_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Returns whether the game was asked to suspend by a signal since the last call.
+pub fn take_suspend_request() -> bool {
+    SUSPEND_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Returns whether the game was continued after being stopped since the last call.
+///
+/// This is the only place the terminal goes back into the game's mode once the game is
+/// running. Whatever stopped the game may have changed the terminal in the meantime, so
+/// it is restored and entered afresh before this returns `true`, which keeps every
+/// request made on entering matched by exactly one undo.
+pub fn take_resume() -> bool {
+    if !RESUMED.swap(false, Ordering::SeqCst) {
+        return false;
+    }
+
+    restore();
+    enter();
+
+    true
+}
+
+/// Hands the terminal back to the shell and stops the game, like `Ctrl-Z` does for any
+/// other program. Returns once the game is continued, with the terminal still handed
+/// back: the next `take_resume` puts it in the game's mode again.
+///
+/// The screen has to be redrawn in full afterwards, as the shell has been using it.
+#[cfg(unix)]
+pub fn suspend() {
+    restore();
+    let _ = signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP);
+
+    // The signal thread may not have seen SIGCONT yet, and the game must not carry on
+    // with the terminal in the shell's mode until it does
+    RESUMED.store(true, Ordering::SeqCst);
+}
+
+/// Suspending is left to the terminal on platforms without job control.
+#[cfg(not(unix))]
+pub fn suspend() {}
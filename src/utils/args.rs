@@ -0,0 +1,233 @@
+use std::{env, fmt};
+
+use crate::map_builders::{BuilderKind, BuilderSchedule};
+
+/// Looks up the value passed after a command-line flag.
+///
+/// Both `--flag value` and `--flag=value` forms are accepted.
+///
+/// ## Arguments
+/// - `flag`: The full flag name, including the leading dashes (e.g. `"--seed"`).
+///
+/// ## Returns
+/// - `Some(value)` if the flag was passed with a value.
+/// - `None` if the flag is missing or has no value after it.
+pub fn get_arg_value(flag: &str) -> Option<String> {
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+
+        if let Some(value) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value.to_string());
+        }
+    }
+
+    None
+}
+
+/// Returns whether a flag that takes no value, such as `--new-game`, was passed.
+pub fn has_flag(flag: &str) -> bool {
+    env::args().skip(1).any(|arg| arg == flag)
+}
+
+/// Why the value passed with `--seed` could not be used as a seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSeed(pub String);
+
+impl fmt::Display for InvalidSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            write!(f, "--seed needs a number after it")
+        } else {
+            write!(f, "--seed needs a whole number from 0 to {}, not `{}`", u64::MAX, self.0)
+        }
+    }
+}
+
+impl std::error::Error for InvalidSeed {}
+
+/// Returns the dungeon seed forced with `--seed <number>`, if any.
+///
+/// ## Returns
+/// - `Ok(Some(seed))` if a valid seed was passed.
+/// - `Ok(None)` if the flag is missing, so each run picks a random seed of its own.
+/// - `Err(error)` if the flag was passed without a number after it, since running with
+///   another seed than the one asked for would defeat the point of passing one.
+pub fn get_seed() -> Result<Option<u64>, InvalidSeed> {
+    match get_arg_value("--seed") {
+        Some(value) => parse_seed(&value).map(Some),
+        None if has_flag("--seed") => Err(InvalidSeed(String::new())),
+        None => Ok(None),
+    }
+}
+
+/// Parses the value passed with `--seed`.
+fn parse_seed(value: &str) -> Result<u64, InvalidSeed> {
+    value.parse().map_err(|_| InvalidSeed(value.to_string()))
+}
+
+/// Why the value passed with `--builder` is not a list of layout algorithms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidBuilder {
+    /// The flag was passed with nothing after it.
+    Missing,
+    /// A name that is none of the layout algorithms.
+    UnknownName(String),
+    /// A floor number that is not a whole number from `1` up.
+    BadDepth(String),
+}
+
+impl fmt::Display for InvalidBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = BuilderKind::ALL.iter().map(|kind| kind.name()).collect();
+
+        match self {
+            InvalidBuilder::Missing => {
+                write!(f, "--builder needs one of {} after it", names.join(", "))
+            }
+            InvalidBuilder::UnknownName(name) => {
+                write!(f, "--builder needs one of {}, not `{}`", names.join(", "), name)
+            }
+            InvalidBuilder::BadDepth(depth) => {
+                write!(f, "--builder needs a floor number from 1 up before `=`, not `{depth}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidBuilder {}
+
+/// Returns the layout algorithms forced with `--builder`.
+///
+/// The value is a comma-separated list. A bare name such as `caves` sets the algorithm
+/// of every floor, and `<depth>=<name>` sets it for a single floor, so
+/// `--builder bsp,1=rooms,5=caves` starts with rooms and corridors, has caves on the
+/// fifth floor and BSP everywhere else. Accepted names are `rooms`, `bsp`, `caves` and
+/// `drunkard`.
+///
+/// ## Returns
+/// - `Ok(schedule)` if the flag is missing, in which case every floor picks its own
+///   algorithm from its seed, or if it was passed a valid list.
+/// - `Err(error)` if the flag was passed without a valid list after it, rather than
+///   quietly generating floors with some other algorithm.
+pub fn get_builder_schedule() -> Result<BuilderSchedule, InvalidBuilder> {
+    match get_arg_value("--builder") {
+        Some(value) => parse_builder_schedule(&value),
+        None if has_flag("--builder") => Err(InvalidBuilder::Missing),
+        None => Ok(BuilderSchedule::default()),
+    }
+}
+
+/// Parses the value passed with `--builder`.
+fn parse_builder_schedule(value: &str) -> Result<BuilderSchedule, InvalidBuilder> {
+    if value.is_empty() {
+        return Err(InvalidBuilder::Missing);
+    }
+
+    let mut default = None;
+    let mut floors = Vec::new();
+
+    for entry in value.split(',') {
+        match entry.split_once('=') {
+            Some((depth, name)) => {
+                let depth = match depth.parse::<usize>() {
+                    Ok(depth) if depth >= 1 => depth,
+                    _ => return Err(InvalidBuilder::BadDepth(depth.to_string())),
+                };
+                floors.push((depth, parse_builder(name)?));
+            }
+            None => default = Some(parse_builder(entry)?),
+        }
+    }
+
+    Ok(floors
+        .into_iter()
+        .fold(BuilderSchedule::every_floor(default), |schedule, (depth, kind)| {
+            schedule.with_floor(depth, kind)
+        }))
+}
+
+/// Parses a single layout algorithm name passed with `--builder`.
+fn parse_builder(name: &str) -> Result<BuilderKind, InvalidBuilder> {
+    BuilderKind::from_name(name).ok_or_else(|| InvalidBuilder::UnknownName(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_seed_accepts_whole_numbers() {
+        assert_eq!(parse_seed("1234"), Ok(1234));
+        assert_eq!(parse_seed("18446744073709551615"), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn parse_seed_rejects_anything_else() {
+        assert_eq!(parse_seed("abc"), Err(InvalidSeed("abc".to_string())));
+        assert!(parse_seed("-1").is_err());
+        assert!(parse_seed("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn parse_builder_accepts_every_name() {
+        for kind in BuilderKind::ALL {
+            assert_eq!(parse_builder(kind.name()), Ok(kind));
+        }
+    }
+
+    #[test]
+    fn parse_builder_lists_the_names_when_misspelt() {
+        let error = parse_builder("cave").unwrap_err();
+
+        assert_eq!(error, InvalidBuilder::UnknownName("cave".to_string()));
+        assert_eq!(
+            error.to_string(),
+            "--builder needs one of rooms, bsp, caves, drunkard, not `cave`"
+        );
+    }
+
+    #[test]
+    fn a_bare_name_sets_every_floor() {
+        let schedule = parse_builder_schedule("caves").unwrap();
+
+        assert_eq!(schedule, BuilderSchedule::every_floor(Some(BuilderKind::CellularAutomata)));
+    }
+
+    #[test]
+    fn floors_can_have_a_builder_of_their_own() {
+        let schedule = parse_builder_schedule("bsp,1=rooms,5=caves").unwrap();
+
+        assert_eq!(schedule.for_depth(1), Some(BuilderKind::RoomsAndCorridors));
+        assert_eq!(schedule.for_depth(2), Some(BuilderKind::Bsp));
+        assert_eq!(schedule.for_depth(5), Some(BuilderKind::CellularAutomata));
+
+        // Without a default, the floors that aren't listed pick their own
+        let schedule = parse_builder_schedule("3=drunkard").unwrap();
+        assert_eq!(schedule.for_depth(3), Some(BuilderKind::DrunkardsWalk));
+        assert_eq!(schedule.for_depth(4), None);
+    }
+
+    #[test]
+    fn parse_builder_schedule_rejects_bad_entries() {
+        assert_eq!(parse_builder_schedule(""), Err(InvalidBuilder::Missing));
+        assert_eq!(
+            parse_builder_schedule("0=caves"),
+            Err(InvalidBuilder::BadDepth("0".to_string()))
+        );
+        assert_eq!(
+            parse_builder_schedule("rooms,two=caves"),
+            Err(InvalidBuilder::BadDepth("two".to_string()))
+        );
+        assert_eq!(
+            parse_builder_schedule("2=cave"),
+            Err(InvalidBuilder::UnknownName("cave".to_string()))
+        );
+    }
+}
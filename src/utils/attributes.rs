@@ -0,0 +1,41 @@
+use std::ops::BitOr;
+
+use serde::{Deserialize, Serialize};
+
+/// Text attributes a glyph is drawn with, combined with `|`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attributes(u8);
+
+impl Attributes {
+    pub const NONE: Attributes = Attributes(0);
+    pub const BOLD: Attributes = Attributes(1);
+    pub const DIM: Attributes = Attributes(1 << 1);
+    pub const ITALIC: Attributes = Attributes(1 << 2);
+    pub const REVERSE: Attributes = Attributes(1 << 3);
+
+    /// Returns whether every attribute in `other` is set.
+    pub fn contains(self, other: Attributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the SGR parameters that switch on each attribute that is set.
+    pub fn sgr_params(self) -> impl Iterator<Item = u8> {
+        [
+            (Attributes::BOLD, 1),
+            (Attributes::DIM, 2),
+            (Attributes::ITALIC, 3),
+            (Attributes::REVERSE, 7),
+        ]
+        .into_iter()
+        .filter(move |(attribute, _)| self.contains(*attribute))
+        .map(|(_, code)| code)
+    }
+}
+
+impl BitOr for Attributes {
+    type Output = Attributes;
+
+    fn bitor(self, rhs: Attributes) -> Attributes {
+        Attributes(self.0 | rhs.0)
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// A fixed-size set of flags packed 64 to a word.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    /// Creates a set of `len` flags, all cleared.
+    pub fn new(len: usize) -> Self {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether flag `idx` is set. Out of range flags are never set.
+    pub fn get(&self, idx: usize) -> bool {
+        idx < self.len && self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    /// Sets flag `idx`. Out of range flags are ignored.
+    pub fn insert(&mut self, idx: usize) {
+        if idx < self.len {
+            self.words[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    /// Clears flag `idx`. Out of range flags are ignored.
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.len {
+            self.words[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    /// Clears every flag.
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+}
@@ -1,4 +1,8 @@
-#[derive(Copy, Clone)]
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Color {
     Default,
     Black,
@@ -9,10 +13,89 @@ pub enum Color {
     Magenta,
     Cyan,
     White,
+    /// An entry of the 256-colour palette.
+    Ansi256(u8),
+    /// A 24-bit colour.
+    Rgb(u8, u8, u8),
+}
+
+/// How many colours the terminal can show.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// The eight basic ANSI colours.
+    Basic,
+    /// The 256-colour palette.
+    Ansi256,
+    /// Full 24-bit colour.
+    TrueColor,
 }
 
+impl ColorSupport {
+    /// Works out what the terminal supports from the `COLORTERM` and `TERM` environment
+    /// variables, falling back to the basic colours when neither says otherwise.
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+
+        Self::from_env(&colorterm, &term)
+    }
+
+    fn from_env(colorterm: &str, term: &str) -> Self {
+        if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Basic
+        }
+    }
+}
+
+/// The levels used by each channel of the 6x6x6 colour cube in the 256-colour palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The basic colours in palette order, with the values xterm shows them as.
+const BASIC_COLORS: [(Color, (u8, u8, u8)); 8] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::White, (229, 229, 229)),
+];
+
 impl Color {
-    pub fn to_code(&self) -> u8 {
+    /// Returns the closest colour the terminal can show with the given `support`.
+    ///
+    /// ## Arguments
+    /// * `support` - What the terminal can show, usually from `ColorSupport::detect`.
+    ///
+    /// ## Returns
+    /// `self` when the terminal can already show it, otherwise the nearest palette entry.
+    pub fn downsample(self, support: ColorSupport) -> Color {
+        match (self, support) {
+            (Color::Rgb(r, g, b), ColorSupport::Ansi256) => Color::Ansi256(Self::rgb_to_ansi256(r, g, b)),
+            (Color::Rgb(..) | Color::Ansi256(_), ColorSupport::Basic) => Self::nearest_basic(self.to_rgb()),
+            _ => self,
+        }
+    }
+
+    /// Returns the parameters of the SGR escape that selects this colour, e.g. `31` for
+    /// a red foreground or `48;5;30` for a palette background.
+    pub fn sgr_params(&self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+
+        match self {
+            Color::Ansi256(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+            basic => (base + basic.to_code()).to_string(),
+        }
+    }
+
+    /// Returns the offset of a basic colour from the start of its SGR range.
+    fn to_code(self) -> u8 {
         match self {
             Color::Default => 9,
             Color::Black => 0,
@@ -23,6 +106,118 @@ impl Color {
             Color::Magenta => 5,
             Color::Cyan => 6,
             Color::White => 7,
+            Color::Ansi256(_) | Color::Rgb(..) => unreachable!("not a basic colour"),
+        }
+    }
+
+    /// Returns roughly how the colour looks in 24-bit. `Default` counts as black.
+    fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Default => (0, 0, 0),
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Ansi256(n @ 0..=7) => BASIC_COLORS[n as usize].1,
+            // The bright colours
+            Color::Ansi256(n @ 8..=15) => {
+                let (r, g, b) = BASIC_COLORS[n as usize - 8].1;
+                (r.saturating_add(50), g.saturating_add(50), b.saturating_add(50))
+            }
+            Color::Ansi256(n @ 16..=231) => {
+                let n = n - 16;
+                (
+                    CUBE_LEVELS[(n / 36) as usize],
+                    CUBE_LEVELS[(n / 6 % 6) as usize],
+                    CUBE_LEVELS[(n % 6) as usize],
+                )
+            }
+            Color::Ansi256(n) => {
+                let grey = 8 + (n - 232) * 10;
+                (grey, grey, grey)
+            }
+            basic => BASIC_COLORS[basic.to_code() as usize].1,
         }
     }
+
+    /// Picks the closest entry of the colour cube or the greyscale ramp.
+    fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+        let level = |c: u8| {
+            (0..CUBE_LEVELS.len())
+                .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+                .unwrap() as u8
+        };
+        let (lr, lg, lb) = (level(r), level(g), level(b));
+        let cube = 16 + 36 * lr + 6 * lg + lb;
+        let cube_rgb = (CUBE_LEVELS[lr as usize], CUBE_LEVELS[lg as usize], CUBE_LEVELS[lb as usize]);
+
+        let average = (r as u32 + g as u32 + b as u32) / 3;
+        let grey_step = (average.saturating_sub(3) / 10).min(23) as u8;
+        let grey = 8 + grey_step * 10;
+
+        if distance((r, g, b), (grey, grey, grey)) < distance((r, g, b), cube_rgb) {
+            232 + grey_step
+        } else {
+            cube
+        }
+    }
+
+    fn nearest_basic(rgb: (u8, u8, u8)) -> Color {
+        BASIC_COLORS
+            .iter()
+            .min_by_key(|(_, basic)| distance(rgb, *basic))
+            .map(|(color, _)| *color)
+            .unwrap()
+    }
+}
+
+/// Squared distance between two colours.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let channel = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsample_keeps_what_the_terminal_can_show() {
+        let teal = Color::Rgb(0, 128, 128);
+
+        assert_eq!(teal.downsample(ColorSupport::TrueColor), teal);
+        assert_eq!(Color::Ansi256(30).downsample(ColorSupport::Ansi256), Color::Ansi256(30));
+        assert_eq!(Color::Red.downsample(ColorSupport::Basic), Color::Red);
+        assert_eq!(Color::Default.downsample(ColorSupport::Basic), Color::Default);
+    }
+
+    #[test]
+    fn downsample_picks_the_nearest_palette_entry() {
+        let teal = Color::Rgb(0, 128, 128);
+
+        assert_eq!(teal.downsample(ColorSupport::Ansi256), Color::Ansi256(30));
+        assert_eq!(teal.downsample(ColorSupport::Basic), Color::Cyan);
+        assert_eq!(Color::Ansi256(196).downsample(ColorSupport::Basic), Color::Red);
+    }
+
+    #[test]
+    fn downsample_sends_greys_to_the_greyscale_ramp() {
+        assert_eq!(Color::Rgb(128, 128, 128).downsample(ColorSupport::Ansi256), Color::Ansi256(244));
+        assert_eq!(Color::Rgb(0, 0, 0).downsample(ColorSupport::Ansi256), Color::Ansi256(16));
+        assert_eq!(Color::Rgb(255, 255, 255).downsample(ColorSupport::Ansi256), Color::Ansi256(231));
+    }
+
+    #[test]
+    fn support_is_read_from_the_environment() {
+        assert_eq!(ColorSupport::from_env("truecolor", "xterm"), ColorSupport::TrueColor);
+        assert_eq!(ColorSupport::from_env("", "xterm-direct"), ColorSupport::TrueColor);
+        assert_eq!(ColorSupport::from_env("", "xterm-256color"), ColorSupport::Ansi256);
+        assert_eq!(ColorSupport::from_env("", "vt100"), ColorSupport::Basic);
+    }
+
+    #[test]
+    fn sgr_params_cover_every_kind_of_colour() {
+        assert_eq!(Color::Red.sgr_params(false), "31");
+        assert_eq!(Color::Default.sgr_params(true), "49");
+        assert_eq!(Color::Ansi256(30).sgr_params(true), "48;5;30");
+        assert_eq!(Color::Rgb(1, 2, 3).sgr_params(false), "38;2;1;2;3");
+    }
 }
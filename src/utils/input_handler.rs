@@ -1,191 +1,315 @@
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
-use std::collections::HashMap;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::collections::{HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::terminal;
+
+/// How long the reader thread waits for an event before checking whether it should stop.
+const POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// A single key press, as it came from the terminal.
+///
+/// ## Fields
+///
+/// * `code`: The key that was pressed.
+/// * `modifiers`: The modifier keys (Shift, Ctrl, Alt, ...) held down with it.
+/// * `repeat`: `true` if the press comes from the key being held down, rather than
+///   from it being pressed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyInput {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+    pub repeat: bool,
+}
+
+impl KeyInput {
+    /// Returns whether this is `Ctrl` plus the character `c`.
+    pub fn is_ctrl(&self, c: char) -> bool {
+        self.modifiers.contains(KeyModifiers::CONTROL) && self.code == KeyCode::Char(c)
+    }
+}
 
 /// A structure for handling keyboard input asynchronously.
 ///
-/// The `InputHandler` struct provides functionality for capturing and managing keyboard input
-/// in a multithreaded environment. It tracks the state of each key (pressed or not) in a thread-safe
-/// manner and operates in a separate thread to continuously listen for and process keyboard events.
+/// The `InputHandler` struct reads keyboard events on a separate thread and queues every
+/// key press, in the order they happened, until the game gets to them. Nothing is lost
+/// or repeated between frames: each press is handed out exactly once.
 ///
 /// ## Fields
 ///
-/// * `key_states`: A shared, thread-safe map that maintains the state of each key. Each key is associated
-///   with a boolean indicating whether it is pressed (`true`) or not (`false`).
+/// * `events`: A shared, thread-safe queue of the key presses that haven't been handled yet.
+/// * `held`: The keys that are down right now. Only tracked in `InputMode::Normal`.
+/// * `resized`: The size the terminal was last resized to, until the game gets to it.
+/// * `input_mode`: Whether the terminal tells us when keys are released.
 /// * `running`: A shared atomic boolean that indicates whether the input handling thread should
 ///   continue running.
+/// * `reads_terminal`: Whether `start` reads events from the terminal. Headless handlers only
+///   get the events pushed into them.
+/// * `reader`: The input handling thread, while it runs.
 pub struct InputHandler {
-    /// A shared, thread-safe map of key states where each key is associated with a boolean
-    /// indicating whether the key is pressed (`true`) or not (`false`).
-    pub key_states: Arc<Mutex<HashMap<KeyCode, bool>>>,
+    /// A shared, thread-safe queue of key presses, oldest first.
+    events: Arc<Mutex<VecDeque<KeyInput>>>,
+
+    /// A shared, thread-safe set of the keys that are held down right now.
+    held: Arc<Mutex<HashSet<KeyCode>>>,
+
+    /// The newest size the terminal reported, if it was resized since the last check.
+    resized: Arc<Mutex<Option<(u16, u16)>>>,
 
-    pub input_mode: Arc<InputMode>,
+    pub input_mode: InputMode,
 
     /// A shared atomic boolean that indicates whether the input handling thread should keep running.
     running: Arc<AtomicBool>,
+
+    /// Whether `start` spawns a thread that reads events from the terminal.
+    reads_terminal: bool,
+
+    /// The input handling thread, joined when it is stopped.
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Default for InputHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InputHandler {
-    /// Creates a new instance of `InputHandler` with initialized states.
-    ///
-    /// This constructor initializes a new `InputHandler` instance with an empty `HashMap` for key states
-    /// and sets the `running` flag to `true`, indicating that the input handling thread should start running.
+    /// Creates a new instance of `InputHandler` with an empty event queue.
     ///
     /// ## Returns
     ///
-    /// Returns an `InputHandler` instance with a new `HashMap` for key states and the running flag set to `true`.
-    ///
-    /// ## Example
-    ///
-    /// ```
-    /// let input_handler = InputHandler::new();
-    /// ```
+    /// Returns an `InputHandler` instance that is ready to be started.
     pub fn new() -> Self {
-        let input_mode = if check_compatibility_mode() {
-            InputMode::Compatibility
-        } else {
-            InputMode::Normal
-        };
-
         Self {
-            key_states: Arc::new(Mutex::new(HashMap::new())),
-            running: Arc::new(AtomicBool::new(true)),
-
-            input_mode: Arc::new(input_mode),
+            events: Arc::new(Mutex::new(VecDeque::new())),
+            held: Arc::new(Mutex::new(HashSet::new())),
+            resized: Arc::new(Mutex::new(None)),
+            input_mode: InputMode::Compatibility,
+            running: Arc::new(AtomicBool::new(false)),
+            reads_terminal: true,
+            reader: None,
         }
     }
 
-    /// Starts a new thread to handle keyboard input.
+    /// Creates an `InputHandler` that never touches the terminal, for running the game
+    /// without a TTY.
     ///
-    /// This method spawns a new thread that continuously listens for keyboard events and updates the
-    /// `key_states` map accordingly. The thread will keep running until `self.running` is set to `false`
-    /// via the `stop` method.
+    /// It only hands out the key events given to it with `push_event`. Key releases are
+    /// reported, as they are in `InputMode::Normal`.
+    pub fn headless() -> Self {
+        let mut handler = Self::new();
+        handler.input_mode = InputMode::Normal;
+        handler.reads_terminal = false;
+
+        handler
+    }
+
+    /// Starts a new thread to handle keyboard input.
     ///
-    /// ## Example
+    /// This method works out the input mode, then spawns a thread that listens for keyboard
+    /// events and queues them, and keeps track of the terminal being resized. The thread
+    /// checks regularly whether it should stop, so it never stays blocked waiting for a key
+    /// once `stop` is called.
     ///
-    /// ```
-    /// let input_handler = InputHandler::new();
-    /// input_handler.start();
-    /// ```
-    pub fn start(&self) {
-        let key_states_input = Arc::clone(&self.key_states);
-        let running_input = Arc::clone(&self.running);
-        let input_mode_threaded = Arc::clone(&self.input_mode);
-
-        thread::spawn(move || {
-            while running_input.load(Ordering::Relaxed) {
-                if let Event::Key(key_event) = event::read().unwrap() {
-                    let mut key_states = key_states_input.lock().unwrap();
-
-                    match key_event.kind {
-                        KeyEventKind::Press => {
-                            // Mark the key as pressed
-                            key_states.insert(key_event.code, true);
-                        }
-                        KeyEventKind::Release => {
-                            if let InputMode::Normal = *input_mode_threaded {
-                                // Remove the key from the map to indicate it's not pressed
-                                key_states.remove(&key_event.code);
-                            }
-                        }
-                        _ => {}
-                    }
+    /// Calling `start` on a handler that is already running, or on a headless one, does
+    /// nothing.
+    pub fn start(&mut self) {
+        if self.reader.is_some() || !self.reads_terminal {
+            return;
+        }
+
+        self.input_mode = detect_input_mode();
+        self.running.store(true, Ordering::Relaxed);
+
+        let events = Arc::clone(&self.events);
+        let held = Arc::clone(&self.held);
+        let resized = Arc::clone(&self.resized);
+        let running = Arc::clone(&self.running);
+        let input_mode = self.input_mode;
+
+        self.reader = Some(thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                // A broken terminal can't give us any more input, so treat it as no input
+                if !event::poll(POLL_TIMEOUT).unwrap_or(false) {
+                    continue;
+                }
+
+                if let Ok(event) = event::read() {
+                    handle_event(event, input_mode, &events, &held, &resized);
                 }
             }
-        });
+        }));
     }
 
-    /// Signals the input handling thread to stop running.
-    ///
-    /// This method sets the `running` flag to `false`, which causes the input handling thread to exit
-    /// its loop and terminate. It should be called when you want to stop capturing keyboard input.
-    ///
-    /// ## Example
+    /// Stops the input handling thread and waits for it to finish.
     ///
-    /// ```
-    /// let input_handler = InputHandler::new();
-    /// input_handler.start();
-    /// // After some time or condition
-    /// input_handler.stop();
-    /// ```
-    pub fn stop(&self) {
+    /// The thread notices within `POLL_TIMEOUT`, so this never blocks for long. Key presses
+    /// that were already queued stay in the queue.
+    pub fn stop(&mut self) {
+        // A reader thread that panicked has already reported it, and the game is on its
+        // way out anyway
+        let _ = self.halt();
+        self.release_keys();
+    }
+
+    /// Returns whether the input handling thread ended without being told to stop, such
+    /// as by panicking. No more input can arrive once it has.
+    pub fn reader_died(&self) -> bool {
+        self.reader.as_ref().is_some_and(|reader| reader.is_finished())
+    }
+
+    /// Tells the input handling thread to stop and waits for it, handing back how it ended.
+    fn halt(&mut self) -> thread::Result<()> {
         // Set `running` to false to stop the thread.
         self.running.store(false, Ordering::Relaxed);
+
+        match self.reader.take() {
+            Some(reader) => reader.join(),
+            None => Ok(()),
+        }
     }
 
-    /// Retrieves the current key states.
-    ///
-    /// This method locks the `key_states` map and returns a guard that provides access to the current
-    /// key states. The key states map contains the state of each key (pressed or not).
+    /// Forgets every key that is held down, for when their releases can't have reached
+    /// the game, e.g. while it was suspended or another window had the focus.
+    pub fn release_keys(&self) {
+        self.held.lock().unwrap().clear();
+    }
+
+    /// Handles `key_event` as if it had just come from the terminal: presses are queued,
+    /// and presses and releases keep the held keys up to date.
+    pub fn push_event(&self, key_event: KeyEvent) {
+        handle_key_event(key_event, self.input_mode, &self.events, &self.held);
+    }
+
+    /// Takes the oldest key press that hasn't been handled yet, without waiting.
     ///
     /// ## Returns
     ///
-    /// Returns a `std::sync::MutexGuard` that provides access to the `HashMap` of key states.
-    ///
-    /// ## Example
-    ///
-    /// ```
-    /// let input_handler = InputHandler::new();
-    /// input_handler.start();
-    /// // Later, get the key states
-    /// let key_states = input_handler.get_key_states();
-    /// ```
-    pub fn get_key_states(&self) -> HashMap<KeyCode, bool> {
-        let key_states: HashMap<KeyCode, bool> = self.key_states.lock().unwrap().clone();
-
-        if let InputMode::Compatibility = *self.input_mode {
-            self.key_states.lock().unwrap().clear();
-        }
+    /// Returns `Some(key)` if a key was pressed since the last call, `None` otherwise.
+    pub fn pop_key(&self) -> Option<KeyInput> {
+        self.events.lock().unwrap().pop_front()
+    }
 
-        key_states
+    /// Throws away every key press that hasn't been handled yet. Keys that are held down
+    /// stay held.
+    pub fn clear_keys(&self) {
+        self.events.lock().unwrap().clear();
     }
 
-    /// Checks if a key was pressed and then marks that we checked if it was pressed already.
-    ///
-    /// This method retrieves the state of the specified `keycode` and, if the key is has not already been presse,
-    /// sets its state to `false` (pressed) and returns `true`. If the key has already been pressed, it returns `false`.
-    ///
-    /// ## Arguments
-    ///
-    /// * `keycode`: The `KeyCode` of the key to check.
+    /// Takes the oldest queued key press that matches `predicate` out of the queue.
+    pub fn take_first(&self, predicate: impl Fn(&KeyInput) -> bool) -> Option<KeyInput> {
+        let mut events = self.events.lock().unwrap();
+        let idx = events.iter().position(predicate)?;
+
+        events.remove(idx)
+    }
+
+    /// Takes the size the terminal was resized to, if it changed since the last call.
     ///
     /// ## Returns
     ///
-    /// Returns `true` if the key was pressed and has been marked as released; otherwise, returns `false`.
-    ///
-    /// ## Example
+    /// Returns `Some((width, height))` with the newest size if the terminal was resized,
+    /// `None` otherwise.
+    pub fn take_resize(&self) -> Option<(u16, u16)> {
+        self.resized.lock().unwrap().take()
+    }
+
+    /// Returns every key that is held down right now.
     ///
-    /// ```
-    /// let input_handler = InputHandler::new();
-    /// input_handler.start();
-    /// // Later, check if a specific key was pressed
-    /// if input_handler.get_key_once(&crossterm::event::KeyCode::Esc) {
-    ///     println!("Escape key was pressed.");
-    /// }
-    /// ```
-    pub fn get_key_once(&self, keycode: &event::KeyCode) -> bool {
-        let mut key_states = self.key_states.lock().unwrap().clone();
-
-        if key_states.get(keycode).unwrap_or(&false) == &true {
-            key_states.insert(*keycode, false);
-            return true;
-        }
+    /// Always empty in `InputMode::Compatibility`, where key releases aren't reported.
+    pub fn held_keys(&self) -> Vec<KeyCode> {
+        self.held.lock().unwrap().iter().copied().collect()
+    }
+}
+
+impl Drop for InputHandler {
+    fn drop(&mut self) {
+        // A reader thread that panicked has already reported it, and panicking again
+        // while dropping would abort the game
+        let _ = self.halt();
+    }
+}
 
-        false
+/// Passes on an event read from the terminal to whatever keeps track of it.
+fn handle_event(
+    event: Event,
+    input_mode: InputMode,
+    events: &Mutex<VecDeque<KeyInput>>,
+    held: &Mutex<HashSet<KeyCode>>,
+    resized: &Mutex<Option<(u16, u16)>>,
+) {
+    match event {
+        Event::Key(key_event) => handle_key_event(key_event, input_mode, events, held),
+        // Only the last size matters when a drag sends a burst of them
+        Event::Resize(width, height) => *resized.lock().unwrap() = Some((width, height)),
+        // Keys let go of in another window never send their release here
+        Event::FocusLost => held.lock().unwrap().clear(),
+        _ => {}
     }
 }
 
+/// Queues a key press, or updates the held keys for a release.
+fn handle_key_event(
+    key_event: KeyEvent,
+    input_mode: InputMode,
+    events: &Mutex<VecDeque<KeyInput>>,
+    held: &Mutex<HashSet<KeyCode>>,
+) {
+    let mut held = held.lock().unwrap();
+
+    let repeat = match key_event.kind {
+        KeyEventKind::Press => match input_mode {
+            // A press for a key that never came back up is the key repeating
+            InputMode::Normal => !held.insert(key_event.code),
+            InputMode::Compatibility => false,
+        },
+        KeyEventKind::Repeat => true,
+        KeyEventKind::Release => {
+            held.remove(&key_event.code);
+            return;
+        }
+    };
+
+    events.lock().unwrap().push_back(KeyInput {
+        code: key_event.code,
+        modifiers: key_event.modifiers,
+        repeat,
+    });
+}
+
 use std::env;
 
+/// Works out whether key releases will be reported, asking the terminal to report them
+/// if it can.
+///
+/// The Windows console always reports them, and other terminals do once they are asked
+/// to through the keyboard enhancement protocol. Terminals that don't support it only
+/// send key presses, so they are handled in compatibility mode, as is everything when
+/// `--compatibility-input` is passed.
+fn detect_input_mode() -> InputMode {
+    if check_compatibility_mode() {
+        return InputMode::Compatibility;
+    }
+
+    if cfg!(windows) || terminal::enable_key_releases() {
+        InputMode::Normal
+    } else {
+        InputMode::Compatibility
+    }
+}
+
 /// An enum representing the input mode.
 ///
 /// This enum defines the modes in which the application can operate:
 ///
-/// - `Normal`: Standard input mode, used for most systems.
-/// - `Compatibility`: Compatibility mode for systems where certain inputs might not work as expected.
-#[derive(Clone, Copy, Debug)]
+/// - `Normal`: The terminal reports when keys are released, so held keys can be tracked.
+/// - `Compatibility`: Compatibility mode for terminals that only report key presses, or
+///   when `--compatibility-input` is passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InputMode {
     Normal,
     Compatibility,
@@ -198,15 +322,6 @@ pub enum InputMode {
 /// ## Returns
 /// - `true` if `--compatibility-input` is passed, indicating that compatibility mode should be used.
 /// - `false` otherwise, meaning the program will use the normal input mode.
-///
-/// ## Example
-/// ```rust
-/// if check_compatibility_mode() {
-///     println!("Compatibility mode enabled.");
-/// } else {
-///     println!("Normal mode.");
-/// }
-/// ```
 fn check_compatibility_mode() -> bool {
     let args: Vec<String> = env::args().collect();
 
@@ -218,3 +333,146 @@ fn check_compatibility_mode() -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEventState;
+
+    use super::*;
+
+    fn event(code: KeyCode, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+            kind,
+            state: KeyEventState::NONE,
+        }
+    }
+
+    fn codes(handler: &InputHandler) -> Vec<KeyCode> {
+        std::iter::from_fn(|| handler.pop_key()).map(|key| key.code).collect()
+    }
+
+    #[test]
+    fn presses_come_out_in_order_exactly_once() {
+        let handler = InputHandler::headless();
+        for c in ['a', 'b', 'a'] {
+            handler.push_event(event(KeyCode::Char(c), KeyEventKind::Press));
+            handler.push_event(event(KeyCode::Char(c), KeyEventKind::Release));
+        }
+
+        assert_eq!(
+            codes(&handler),
+            [KeyCode::Char('a'), KeyCode::Char('b'), KeyCode::Char('a')]
+        );
+        assert_eq!(handler.pop_key(), None);
+    }
+
+    #[test]
+    fn held_keys_follow_presses_and_releases() {
+        let handler = InputHandler::headless();
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Press));
+        handler.push_event(event(KeyCode::Right, KeyEventKind::Press));
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Release));
+
+        assert_eq!(handler.held_keys(), [KeyCode::Right]);
+    }
+
+    #[test]
+    fn a_second_press_without_a_release_is_a_repeat() {
+        let handler = InputHandler::headless();
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Press));
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Press));
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Repeat));
+
+        let repeats: Vec<bool> = std::iter::from_fn(|| handler.pop_key())
+            .map(|key| key.repeat)
+            .collect();
+        assert_eq!(repeats, [false, true, true]);
+    }
+
+    #[test]
+    fn compatibility_mode_never_holds_keys() {
+        let mut handler = InputHandler::headless();
+        handler.input_mode = InputMode::Compatibility;
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Press));
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Press));
+
+        assert!(handler.pop_key().is_some_and(|key| !key.repeat));
+        assert!(handler.pop_key().is_some_and(|key| !key.repeat));
+        assert!(handler.held_keys().is_empty());
+    }
+
+    #[test]
+    fn take_first_leaves_the_other_presses_queued() {
+        let handler = InputHandler::headless();
+        for code in [KeyCode::Char('a'), KeyCode::Esc, KeyCode::Char('b'), KeyCode::Esc] {
+            handler.push_event(event(code, KeyEventKind::Press));
+            handler.push_event(event(code, KeyEventKind::Release));
+        }
+
+        assert!(handler.take_first(|key| key.code == KeyCode::Esc).is_some());
+        assert_eq!(
+            codes(&handler),
+            [KeyCode::Char('a'), KeyCode::Char('b'), KeyCode::Esc]
+        );
+    }
+
+    #[test]
+    fn losing_the_focus_releases_every_key() {
+        let handler = InputHandler::headless();
+        handler.push_event(event(KeyCode::Up, KeyEventKind::Press));
+        handler.push_event(event(KeyCode::Right, KeyEventKind::Press));
+        handle_event(
+            Event::FocusLost,
+            handler.input_mode,
+            &handler.events,
+            &handler.held,
+            &handler.resized,
+        );
+
+        assert!(handler.held_keys().is_empty());
+        assert_eq!(codes(&handler), [KeyCode::Up, KeyCode::Right]);
+    }
+
+    #[test]
+    fn a_panicked_reader_does_not_panic_the_drop() {
+        let mut handler = InputHandler::headless();
+        handler.reader = Some(thread::spawn(|| panic!("the terminal went away")));
+
+        drop(handler);
+    }
+
+    #[test]
+    fn a_panicked_reader_does_not_panic_the_stop() {
+        let mut handler = InputHandler::headless();
+        handler.reader = Some(thread::spawn(|| panic!("the terminal went away")));
+
+        handler.stop();
+
+        assert!(handler.reader.is_none());
+    }
+
+    #[test]
+    fn a_panicked_reader_is_noticed() {
+        let mut handler = InputHandler::headless();
+        assert!(!handler.reader_died());
+
+        handler.reader = Some(thread::spawn(|| panic!("the terminal went away")));
+        while !handler.reader.as_ref().unwrap().is_finished() {
+            thread::yield_now();
+        }
+
+        assert!(handler.reader_died());
+        handler.stop();
+        assert!(!handler.reader_died());
+    }
+
+    #[test]
+    fn a_headless_handler_never_starts_reading() {
+        let mut handler = InputHandler::headless();
+        handler.start();
+
+        assert!(handler.reader.is_none());
+    }
+}
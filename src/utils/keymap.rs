@@ -0,0 +1,265 @@
+use std::{collections::HashMap, fmt};
+
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+
+use crate::components::position::Position;
+
+/// Something the player can ask for with a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveN,
+    MoveS,
+    MoveE,
+    MoveW,
+    MoveNe,
+    MoveNw,
+    MoveSe,
+    MoveSw,
+    Wait,
+    Descend,
+    Ascend,
+    PickUp,
+    Inventory,
+    Drop,
+    Equipment,
+}
+
+impl Action {
+    /// Every action, in the order they are listed in the settings file.
+    pub const ALL: [Action; 15] = [
+        Action::MoveN,
+        Action::MoveS,
+        Action::MoveE,
+        Action::MoveW,
+        Action::MoveNe,
+        Action::MoveNw,
+        Action::MoveSe,
+        Action::MoveSw,
+        Action::Wait,
+        Action::Descend,
+        Action::Ascend,
+        Action::PickUp,
+        Action::Inventory,
+        Action::Drop,
+        Action::Equipment,
+    ];
+
+    /// Returns the keys the action is bound to when the settings file doesn't say otherwise.
+    ///
+    /// Besides the arrows, WASD and vi-keys, movement is bound to the numpad both with
+    /// Num Lock on (digits) and off (the keys printed under the digits).
+    pub fn default_keys(&self) -> &'static [KeyCode] {
+        match self {
+            Action::MoveN => &[
+                KeyCode::Up,
+                KeyCode::Char('w'),
+                KeyCode::Char('k'),
+                KeyCode::Char('8'),
+            ],
+            Action::MoveS => &[
+                KeyCode::Down,
+                KeyCode::Char('s'),
+                KeyCode::Char('j'),
+                KeyCode::Char('2'),
+            ],
+            Action::MoveE => &[
+                KeyCode::Right,
+                KeyCode::Char('d'),
+                KeyCode::Char('l'),
+                KeyCode::Char('6'),
+            ],
+            Action::MoveW => &[
+                KeyCode::Left,
+                KeyCode::Char('a'),
+                KeyCode::Char('h'),
+                KeyCode::Char('4'),
+            ],
+            Action::MoveNe => &[KeyCode::Char('u'), KeyCode::Char('9'), KeyCode::PageUp],
+            Action::MoveNw => &[KeyCode::Char('y'), KeyCode::Char('7'), KeyCode::Home],
+            Action::MoveSe => &[KeyCode::Char('n'), KeyCode::Char('3'), KeyCode::PageDown],
+            Action::MoveSw => &[KeyCode::Char('b'), KeyCode::Char('1'), KeyCode::End],
+            Action::Wait => &[KeyCode::Char('.'), KeyCode::Char('5')],
+            Action::Descend => &[KeyCode::Char('>')],
+            Action::Ascend => &[KeyCode::Char('<')],
+            Action::PickUp => &[KeyCode::Char('g'), KeyCode::Char(',')],
+            Action::Inventory => &[KeyCode::Char('i')],
+            Action::Drop => &[KeyCode::Char('x')],
+            Action::Equipment => &[KeyCode::Char('e')],
+        }
+    }
+
+    /// Returns the step a movement action takes, with `+y` pointing north, or `None` for
+    /// actions that aren't movement.
+    pub fn direction(&self) -> Option<Position> {
+        let (x, y) = match self {
+            Action::MoveN => (0, 1),
+            Action::MoveS => (0, -1),
+            Action::MoveE => (1, 0),
+            Action::MoveW => (-1, 0),
+            Action::MoveNe => (1, 1),
+            Action::MoveNw => (-1, 1),
+            Action::MoveSe => (1, -1),
+            Action::MoveSw => (-1, -1),
+            Action::Wait
+            | Action::Descend
+            | Action::Ascend
+            | Action::PickUp
+            | Action::Inventory
+            | Action::Drop
+            | Action::Equipment => return None,
+        };
+
+        Some(Position { x, y })
+    }
+}
+
+/// A key name in the settings file that doesn't match any key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey(pub String);
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown key \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for UnknownKey {}
+
+/// Maps the keys the player presses to the actions they stand for.
+///
+/// A key can only ever stand for one action, but an action can have any number of keys.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<KeyCode, Action>,
+}
+
+impl Default for Keymap {
+    /// Creates a keymap with every action bound to its default keys.
+    fn default() -> Self {
+        Keymap::with_overrides(&HashMap::new())
+    }
+}
+
+impl Keymap {
+    /// Creates a keymap from the default bindings, replacing the keys of every action
+    /// listed in `overrides`.
+    ///
+    /// ## Arguments
+    /// - `overrides`: The keys to use for some actions, e.g. as read from the settings file.
+    ///
+    /// ## Returns
+    /// A keymap where the listed actions use only the given keys, and every other action
+    /// keeps its defaults. When a key is given to more than one action, the override wins.
+    pub fn with_overrides(overrides: &HashMap<Action, Vec<KeyCode>>) -> Self {
+        let mut bindings = HashMap::new();
+
+        for action in Action::ALL {
+            if overrides.contains_key(&action) {
+                continue;
+            }
+
+            for key in action.default_keys() {
+                bindings.insert(*key, action);
+            }
+        }
+
+        for (action, keys) in overrides {
+            for key in keys {
+                bindings.insert(*key, *action);
+            }
+        }
+
+        Keymap { bindings }
+    }
+
+    /// Returns the action `key` is bound to, if any.
+    pub fn action_for(&self, key: &KeyCode) -> Option<Action> {
+        self.bindings.get(key).copied()
+    }
+}
+
+/// Parses a key name from the settings file.
+///
+/// Single characters stand for themselves (`"k"`, `">"`), everything else is matched
+/// by name, ignoring case (`"Up"`, `"PageDown"`, `"F5"`, `"Space"`).
+pub fn parse_key(name: &str) -> Result<KeyCode, UnknownKey> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+
+    let key = match name.to_ascii_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "insert" => KeyCode::Insert,
+        "delete" => KeyCode::Delete,
+        other => match other.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => return Err(UnknownKey(name.to_string())),
+        },
+    };
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_takes_single_characters_as_they_are() {
+        assert_eq!(parse_key("k"), Ok(KeyCode::Char('k')));
+        assert_eq!(parse_key("K"), Ok(KeyCode::Char('K')));
+        assert_eq!(parse_key(">"), Ok(KeyCode::Char('>')));
+        assert_eq!(parse_key("é"), Ok(KeyCode::Char('é')));
+    }
+
+    #[test]
+    fn parse_key_matches_names_ignoring_case() {
+        assert_eq!(parse_key("Up"), Ok(KeyCode::Up));
+        assert_eq!(parse_key("PAGEDOWN"), Ok(KeyCode::PageDown));
+        assert_eq!(parse_key("escape"), Ok(KeyCode::Esc));
+        assert_eq!(parse_key("Space"), Ok(KeyCode::Char(' ')));
+        assert_eq!(parse_key("F5"), Ok(KeyCode::F(5)));
+        assert_eq!(parse_key("f12"), Ok(KeyCode::F(12)));
+    }
+
+    #[test]
+    fn parse_key_rejects_unknown_names() {
+        for name in ["", "Upp", "F0", "F13", "Ctrl"] {
+            assert_eq!(parse_key(name), Err(UnknownKey(name.to_string())));
+        }
+    }
+
+    #[test]
+    fn overrides_replace_only_their_own_actions() {
+        let overrides = HashMap::from([(Action::Wait, vec![KeyCode::Char('z')])]);
+        let keymap = Keymap::with_overrides(&overrides);
+
+        assert_eq!(keymap.action_for(&KeyCode::Char('z')), Some(Action::Wait));
+        assert_eq!(keymap.action_for(&KeyCode::Char('.')), None);
+        assert_eq!(keymap.action_for(&KeyCode::Up), Some(Action::MoveN));
+    }
+
+    #[test]
+    fn overrides_win_over_default_keys() {
+        let overrides = HashMap::from([(Action::Drop, vec![KeyCode::Char('k')])]);
+        let keymap = Keymap::with_overrides(&overrides);
+
+        assert_eq!(keymap.action_for(&KeyCode::Char('k')), Some(Action::Drop));
+        assert_eq!(keymap.action_for(&KeyCode::Up), Some(Action::MoveN));
+    }
+}
@@ -1,4 +1,8 @@
+pub mod args;
+pub mod attributes;
+pub mod bitset;
 pub mod color;
 pub mod input_handler;
+pub mod keymap;
 pub mod rectangle;
-// pub mod settings;
+pub mod settings;
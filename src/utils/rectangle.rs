@@ -21,4 +21,9 @@ impl Rectangle {
             y: ( self.p1.y + self.p2.y ) / 2
         }
     }
+
+    /// Returns whether `pos` is inside the rectangle, `p2` excluded.
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x >= self.p1.x && pos.x < self.p2.x && pos.y >= self.p1.y && pos.y < self.p2.y
+    }
 }
\ No newline at end of file
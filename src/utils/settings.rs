@@ -1,41 +1,223 @@
-use crossterm::event::KeyCode;
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{
+    args,
+    keymap::{parse_key, Action, Keymap, UnknownKey},
+};
+
+/// The folder inside the user's config directory that holds the settings file.
+const CONFIG_DIR_NAME: &str = "terminal-adventure";
+/// The name of the settings file.
+const SETTINGS_FILE_NAME: &str = "settings.toml";
 
 /// A struct representing all the settings for the player.
 ///
-/// This struct contains key bindings for player movement and the input mode used for handling inputs.
-///
 /// ## Fields
-/// - `move_up`: The key used to move the player up.
-/// - `move_right`: The key used to move the player right.
-/// - `move_left`: The key used to move the player left.
-/// - `move_down`: The key used to move the player down.
-/// - `input_mode`: The input mode, which determines how inputs are processed.
-#[derive(Clone, Copy)]
+/// - `keymap`: Which keys trigger which actions.
+/// - `cut_corners`: Whether diagonal steps may squeeze past the corner of a wall.
+/// - `path`: The settings file that `save` writes to, if there is one.
+#[derive(Debug, Clone, Default)]
 pub struct Settings {
-    pub move_up: KeyCode,
-    pub move_right: KeyCode,
-    pub move_left: KeyCode,
-    pub move_down: KeyCode,
+    pub keymap: Keymap,
+    pub cut_corners: bool,
+    pub path: Option<PathBuf>,
+}
+
+/// The settings file as it is written on disk.
+///
+/// ## Example
+/// ```toml
+/// [keys]
+/// move_n = ["Up", "k"]
+/// wait = [".", "Space"]
+///
+/// [movement]
+/// cut_corners = true
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsFile {
+    keys: HashMap<Action, Vec<String>>,
+    movement: MovementSettings,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MovementSettings {
+    cut_corners: bool,
 }
 
+/// Why the settings file could not be used.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The file exists but could not be read.
+    Io(PathBuf, io::Error),
+    /// The file is not valid TOML, or has entries that aren't settings.
+    Parse(PathBuf, toml::de::Error),
+    /// A key binding names a key that doesn't exist.
+    Key(PathBuf, UnknownKey),
+    /// The settings could not be written out as TOML.
+    Write(PathBuf, toml::ser::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            SettingsError::Parse(path, error) => write!(f, "{}: {}", path.display(), error),
+            SettingsError::Key(path, error) => write!(f, "{}: {}", path.display(), error),
+            SettingsError::Write(path, error) => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
 impl Settings {
-    /// Creates a `Settings` instance with default key bindings and an appropriate input mode.
+    /// Loads the settings from the settings file, falling back to the defaults.
     ///
-    /// The input mode is determined by checking if the `--compatibility-input` argument is passed.
+    /// The file is read from the path passed with `--settings <path>`, or else from
+    /// `settings.toml` in the `terminal-adventure` folder of the user's config directory
+    /// (e.g. `~/.config/terminal-adventure/settings.toml` on Linux). Anything the file
+    /// leaves out keeps its default.
     ///
     /// ## Returns
-    /// A `Settings` struct initialized with default values:
-    /// - `move_up` is bound to `KeyCode::Char('w')`.
-    /// - `move_right` is bound to `KeyCode::Char('d')`.
-    /// - `move_left` is bound to `KeyCode::Char('a')`.
-    /// - `move_down` is bound to `KeyCode::Char('s')`.
-    /// - `input_mode` is set based on the presence of the `--compatibility-input` argument.
-    pub fn default() -> Self {
-        Settings {
-            move_up: KeyCode::Char('w'),
-            move_right: KeyCode::Char('d'),
-            move_left: KeyCode::Char('a'),
-            move_down: KeyCode::Char('s'),
+    /// - `Ok(settings)` if the file was read, or if there is no settings file at the
+    ///   default location.
+    /// - `Err(error)` if the file could not be read or has a mistake in it.
+    pub fn load() -> Result<Self, SettingsError> {
+        if let Some(path) = args::get_arg_value("--settings") {
+            return Self::load_from(Path::new(&path));
         }
+
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            // Changes made in the game still get saved, to a new file
+            path => Ok(Settings {
+                path,
+                ..Settings::default()
+            }),
+        }
+    }
+
+    /// Returns where the settings file lives by default, if the platform has a config
+    /// directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(SETTINGS_FILE_NAME))
+    }
+
+    /// Loads the settings from the file at `path`.
+    ///
+    /// ## Arguments
+    /// - `path`: The settings file to read.
+    ///
+    /// ## Returns
+    /// - `Ok(settings)` if the file could be read and every entry in it is valid.
+    /// - `Err(error)` otherwise, naming the file and what is wrong with it.
+    pub fn load_from(path: &Path) -> Result<Self, SettingsError> {
+        let text = fs::read_to_string(path).map_err(|e| SettingsError::Io(path.into(), e))?;
+        let file: SettingsFile =
+            toml::from_str(&text).map_err(|e| SettingsError::Parse(path.into(), e))?;
+
+        let mut overrides: HashMap<Action, Vec<_>> = HashMap::new();
+        for (action, names) in file.keys {
+            let keys = names
+                .iter()
+                .map(|name| parse_key(name))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| SettingsError::Key(path.into(), e))?;
+
+            overrides.insert(action, keys);
+        }
+
+        Ok(Settings {
+            keymap: Keymap::with_overrides(&overrides),
+            cut_corners: file.movement.cut_corners,
+            path: Some(path.into()),
+        })
+    }
+
+    /// Writes the settings that can be changed in the game to the settings file.
+    ///
+    /// Only the entries the game changes are rewritten, so the key bindings are left as
+    /// they were written. Settings without a file, as in tests, are not saved anywhere.
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let io_error = |e| SettingsError::Io(path.clone(), e);
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(io_error(error)),
+        };
+        let mut table: toml::Table =
+            toml::from_str(&text).map_err(|e| SettingsError::Parse(path.clone(), e))?;
+
+        let movement = table
+            .entry("movement")
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if let toml::Value::Table(movement) = movement {
+            movement.insert("cut_corners".to_string(), self.cut_corners.into());
+        }
+
+        let text = toml::to_string(&table).map_err(|e| SettingsError::Write(path.clone(), e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        fs::write(path, text).map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crossterm::event::KeyCode;
+
+    use super::*;
+
+    /// A settings file path of its own for the test called `name`.
+    fn temp_settings(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("roguelike-{}-{name}.toml", std::process::id()))
+    }
+
+    #[test]
+    fn saving_keeps_the_key_bindings() {
+        let path = temp_settings("keeps-keys");
+        fs::write(&path, "[keys]\nwait = [\"z\"]\n").unwrap();
+
+        let mut settings = Settings::load_from(&path).unwrap();
+        settings.cut_corners = true;
+        settings.save().unwrap();
+
+        let loaded = Settings::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.cut_corners);
+        assert_eq!(loaded.keymap.action_for(&KeyCode::Char('z')), Some(Action::Wait));
+    }
+
+    #[test]
+    fn saving_creates_a_missing_file() {
+        let path = temp_settings("missing");
+        let settings = Settings {
+            cut_corners: true,
+            path: Some(path.clone()),
+            ..Settings::default()
+        };
+
+        settings.save().unwrap();
+        let loaded = Settings::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.cut_corners);
     }
 }